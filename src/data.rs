@@ -1,5 +1,147 @@
 
-#[derive(Debug)]
+use crate::source_map::Span;
+use crate::parsing::type_parser::Type;
+
+#[derive(Debug, Clone)]
 pub enum Ast { 
+    Fun(Fun),
+    Data(Data),
+    /// A definition that failed to parse.
+    Error(Span),
+} 
+
+#[derive(Debug, Clone)]
+pub struct Fun {
+    pub meta : Span,
+    /// The doc comments in front of the definition, one line each.
+    pub doc : Option<String>,
+    pub name : String,
+    pub params : Vec<Param>,
+    pub return_type : Option<Type>,
+    pub body : Expr,
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub meta : Span,
+    pub name : String,
+    pub param_type : Option<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Data {
+    pub meta : Span,
+    pub doc : Option<String>,
+    pub name : String,
+    pub type_params : Vec<TypeParam>,
+    pub constructors : Vec<Constructor>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeParam {
+    pub meta : Span,
+    pub name : String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Constructor {
+    pub meta : Span,
+    pub name : String,
+    pub fields : Vec<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    /// Integer literals are kept exact, other numbers are floats.
+    Integer(Span, i64),
+    Number(Span, f64),
+    String(Span, String),
+    Char(Span, char),
+    /// A string with holes, ie "hello {name}".
+    Interpolation(Span, Vec<StringPart>),
+    Bool(Span, bool),
+    Variable(Span, String),
+    Constructor(Span, String, Vec<Expr>),
+    Tuple(Span, Vec<Expr>),
+    List(Span, Vec<Expr>),
+    Call(Span, Box<Expr>, Vec<Expr>),
+    Block(Span, Vec<Stmt>, Option<Box<Expr>>),
+    Match(Span, Box<Expr>, Vec<MatchArm>),
+    Lambda(Span, Vec<Param>, Option<Type>, Box<Expr>),
+    /// A statement that failed to parse.
+    Error(Span),
+}
+
+#[derive(Debug, Clone)]
+pub enum StringPart {
+    Text(String),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Let { meta : Span, name : String, let_type : Option<Type>, value : Expr },
+    Expr(Expr),
+}
+
+impl Expr {
+    pub fn meta(&self) -> Span {
+        match self {
+            Expr::Integer(m, _) => *m,
+            Expr::Number(m, _) => *m,
+            Expr::String(m, _) => *m,
+            Expr::Char(m, _) => *m,
+            Expr::Interpolation(m, _) => *m,
+            Expr::Bool(m, _) => *m,
+            Expr::Variable(m, _) => *m,
+            Expr::Constructor(m, _, _) => *m,
+            Expr::Tuple(m, _) => *m,
+            Expr::List(m, _) => *m,
+            Expr::Call(m, _, _) => *m,
+            Expr::Block(m, _, _) => *m,
+            Expr::Match(m, _, _) => *m,
+            Expr::Lambda(m, _, _, _) => *m,
+            Expr::Error(m) => *m,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub meta : Span,
+    pub pattern : Pattern,
+    pub body : Expr,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Integer(Span, i64),
+    Number(Span, f64),
+    String(Span, String),
+    Char(Span, char),
+    Bool(Span, bool),
+    Variable(Span, String),
+    Wildcard(Span),
+    Constructor(Span, String, Vec<Pattern>),
+    Tuple(Span, Vec<Pattern>),
+    // [a, b] matches a list of exactly two items while [a, b | rest] matches 
+    // a list of at least two items and binds the remainder to rest.
+    List(Span, Vec<Pattern>, Option<Box<Pattern>>),
+}
 
-} 
\ No newline at end of file
+impl Pattern {
+    pub fn meta(&self) -> Span {
+        match self {
+            Pattern::Integer(m, _) => *m,
+            Pattern::Number(m, _) => *m,
+            Pattern::String(m, _) => *m,
+            Pattern::Char(m, _) => *m,
+            Pattern::Bool(m, _) => *m,
+            Pattern::Variable(m, _) => *m,
+            Pattern::Wildcard(m) => *m,
+            Pattern::Constructor(m, _, _) => *m,
+            Pattern::Tuple(m, _) => *m,
+            Pattern::List(m, _, _) => *m,
+        }
+    }
+}
@@ -0,0 +1,236 @@
+
+use crate::source_map::{Span, SourceMap, FileId, Location};
+use crate::json::quote;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+}
+
+/// The phase that produced a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Tokenize,
+    Parse,
+    Type,
+    Runtime,
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub meta : Span,
+    pub message : String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub meta : Span,
+    pub message : String,
+    pub replacement : String,
+}
+
+/// Error codes are stable so that tooling can rely on them:
+///
+/// * E00xx tokenization
+/// * E01xx parsing
+/// * E02xx type checking
+/// * E03xx runtime
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity : Severity,
+    pub phase : Phase,
+    pub code : &'static str,
+    pub message : String,
+    pub primary : Option<Span>,
+    pub labels : Vec<Label>,
+    pub notes : Vec<String>,
+    pub fix : Option<Fix>,
+}
+
+impl Diagnostic {
+    pub fn error(phase : Phase, code : &'static str, message : impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            phase,
+            code,
+            message: message.into(),
+            primary: None,
+            labels: vec![],
+            notes: vec![],
+            fix: None,
+        }
+    }
+
+    pub fn at(mut self, meta : Span) -> Diagnostic {
+        self.primary = Some(meta);
+        self
+    }
+
+    pub fn label(mut self, meta : Span, message : impl Into<String>) -> Diagnostic {
+        self.labels.push(Label { meta, message: message.into() });
+        self
+    }
+
+    pub fn note(mut self, note : impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn fix(mut self, meta : Span, message : impl Into<String>, replacement : impl Into<String>) -> Diagnostic {
+        self.fix = Some(Fix { meta, message: message.into(), replacement: replacement.into() });
+        self
+    }
+
+    /// The file of the primary span.
+    pub fn file(&self) -> Option<FileId> {
+        self.primary.map(|s| s.file)
+    }
+
+    /// Renders the diagnostic with the source lines of each span.
+    pub fn render(&self, sources : &SourceMap) -> String {
+        let phase = match self.phase {
+            Phase::Tokenize => "tokenization",
+            Phase::Parse => "parse",
+            Phase::Type => "type",
+            Phase::Runtime => "runtime",
+        };
+        let severity = self.severity_name();
+
+        let mut ret = match self.primary {
+            Some(span) => {
+                let Location { line, column } = sources.location(span);
+                format!("Encountered {phase} {severity} at line {line} and column {column}: {}\n\n{}", self.message, sources.display(span))
+            },
+            None => format!("Encountered {phase} {severity}: {}", self.message),
+        };
+        for Label { meta, message } in &self.labels {
+            let Location { line, column } = sources.location(*meta);
+            // Labels usually point into the same file as the error, so the file is
+            // only named when it is a different one.
+            let file = match self.file() {
+                Some(f) if f != meta.file => format!(" of {}", sources.name(meta.file)),
+                _ => String::new(),
+            };
+            ret.push_str(&format!("\n\n{message} at line {line} and column {column}{file}:\n\n{}", sources.display(*meta)));
+        }
+        for note in &self.notes {
+            ret.push_str(&format!("\n\nnote: {note}"));
+        }
+        if let Some(Fix { message, replacement, .. }) = &self.fix {
+            ret.push_str(&format!("\n\nhelp: {message}: '{replacement}'"));
+        }
+        ret
+    }
+
+    /// Renders the diagnostic on a single line:  'file:line:column: error[code]: message'.
+    pub fn render_short(&self, sources : &SourceMap) -> String {
+        match self.primary {
+            Some(span) => {
+                let Location { line, column } = sources.location(span);
+                format!("{}:{line}:{column}: {}[{}]: {}", sources.name(span.file), self.severity_name(), self.code, self.message)
+            },
+            None => format!("{}[{}]: {}", self.severity_name(), self.code, self.message),
+        }
+    }
+
+    /// Renders the diagnostic as a single line json object.
+    pub fn render_json(&self, sources : &SourceMap) -> String {
+        fn location(sources : &SourceMap, span : &Span) -> String {
+            let Location { line, column } = sources.location(*span);
+            format!("\"file\":{},\"start\":{},\"end\":{},\"line\":{},\"column\":{}",
+                quote(sources.name(span.file)), span.start, span.end, line, column)
+        }
+
+        let mut fields = vec![
+            format!("\"severity\":\"{}\"", self.severity_name()),
+            format!("\"code\":\"{}\"", self.code),
+            format!("\"message\":{}", quote(&self.message)),
+        ];
+        if let Some(span) = &self.primary {
+            fields.push(location(sources, span));
+        }
+        let labels = self.labels.iter()
+            .map(|l| format!("{{\"message\":{},{}}}", quote(&l.message), location(sources, &l.meta)))
+            .collect::<Vec<_>>();
+        fields.push(format!("\"labels\":[{}]", labels.join(",")));
+        let notes = self.notes.iter().map(|n| quote(n)).collect::<Vec<_>>();
+        fields.push(format!("\"notes\":[{}]", notes.join(",")));
+        match &self.fix {
+            Some(f) => fields.push(format!("\"fix\":{{\"message\":{},\"replacement\":{},{}}}",
+                quote(&f.message), quote(&f.replacement), location(sources, &f.meta))),
+            None => fields.push("\"fix\":null".into()),
+        }
+        format!("{{{}}}", fields.join(","))
+    }
+
+    fn severity_name(&self) -> &'static str {
+        match self.severity {
+            Severity::Error => "error",
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.primary {
+            Some(Span { file, start, end }) => write!(f, "{}[{}] at {}:{}..{}: {}", self.severity_name(), self.code, file.0, start, end, self.message),
+            None => write!(f, "{}[{}]: {}", self.severity_name(), self.code, self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn d(file : FileId) -> Diagnostic {
+        Diagnostic::error(Phase::Type, "E0201", "expected Number but found String")
+            .at(Span { file, start: 20, end: 22 })
+            .label(Span { file, start: 13, end: 15 }, "Expected type comes from here")
+            .note("a note")
+    }
+
+    #[test]
+    fn should_render_with_source() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("a.dour", "fun main() { add(1, \"x\") }");
+        let output = d(file).render(&sources);
+
+        assert!( output.starts_with("Encountered type error at line 1 and column 21: expected Number but found String") );
+        assert!( output.contains("Expected type comes from here at line 1 and column 14:") );
+        assert!( output.ends_with("note: a note") );
+    }
+
+    #[test]
+    fn should_name_label_file_when_different() {
+        let mut sources = SourceMap::new();
+        let a = sources.add("a.dour", "fun f(x : Number) { x }");
+        let b = sources.add("b.dour", "fun main() { f(\"x\") }");
+        let output = Diagnostic::error(Phase::Type, "E0201", "expected Number but found String")
+            .at(Span { file: b, start: 15, end: 17 })
+            .label(Span { file: a, start: 10, end: 15 }, "Expected type comes from here")
+            .render(&sources);
+
+        assert!( output.contains("Expected type comes from here at line 1 and column 11 of a.dour:") );
+    }
+
+    #[test]
+    fn should_render_short() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("a.dour", "fun main() { add(1, \"x\") }");
+
+        assert_eq!( d(file).render_short(&sources), "a.dour:1:21: error[E0201]: expected Number but found String" );
+    }
+
+    #[test]
+    fn should_render_json() {
+        let mut sources = SourceMap::new();
+        let file = sources.add("a\"b.dour", "fun main() { add(1, \"x\") }");
+        let output = d(file).fix(Span { file, start: 20, end: 22 }, "use a number", "1").render_json(&sources);
+
+        assert!( output.starts_with("{\"severity\":\"error\",\"code\":\"E0201\",") );
+        assert!( output.contains("\"file\":\"a\\\"b.dour\",\"start\":20,\"end\":22,\"line\":1,\"column\":21") );
+        assert!( output.contains("\"notes\":[\"a note\"]") );
+        assert!( output.contains("\"fix\":{\"message\":\"use a number\",\"replacement\":\"1\"") );
+    }
+}
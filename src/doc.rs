@@ -0,0 +1,223 @@
+
+use std::collections::HashMap;
+
+use crate::data::{Ast, Fun, Data};
+use crate::parsing::type_parser::Type;
+
+const STYLE : &str = "\
+body { font-family: sans-serif; max-width: 50em; margin: 2em auto; padding: 0 1em; line-height: 1.5; }
+code { font-family: monospace; }
+nav { margin-bottom: 2em; }
+section { margin-bottom: 2em; }
+h3 { margin-bottom: 0.25em; }
+.undocumented { color: #888; }
+";
+
+/// The definitions of one source file.
+pub struct Module<'a> {
+    pub name : String,
+    pub asts : &'a [Ast],
+}
+
+/// Names a module after its file:  'lib/list.dr' is 'lib.list'.
+pub fn module_name(file : &str) -> String {
+    let file = file.strip_prefix("./").unwrap_or(file);
+    let file = file.strip_suffix(".dr").unwrap_or(file);
+    file.trim_matches(|c| c == '<' || c == '>').replace(['/', '\\'], ".")
+}
+
+/// Renders the documentation as (file name, contents) pairs:  an index, a page
+/// for each module and a style sheet.  Function signatures use the types from
+/// type_of (ie the checker) so that functions without annotations still show
+/// their whole type.  Type names link to the data declaration defining them.
+pub fn generate(modules : &[Module], type_of : impl Fn(&str) -> Option<Type>) -> Vec<(String, String)> {
+    let mut links = HashMap::new();
+    for module in modules {
+        for ast in module.asts {
+            if let Ast::Data(d) = ast {
+                links.insert(d.name.clone(), format!("{}#{}", page_name(&module.name), d.name));
+            }
+        }
+    }
+
+    let mut ret = vec![("index.html".to_string(), index(modules)), ("style.css".to_string(), STYLE.to_string())];
+    for module in modules {
+        ret.push((page_name(&module.name), module_page(module, &links, &type_of)));
+    }
+    ret
+}
+
+fn page_name(module : &str) -> String {
+    format!("module-{}.html", module)
+}
+
+fn page(title : &str, body : &str) -> String {
+    format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <link rel=\"stylesheet\" href=\"style.css\">\n</head>\n<body>\n{}</body>\n</html>\n", escape(title), body)
+}
+
+fn index(modules : &[Module]) -> String {
+    let mut body = String::from("<h1>Modules</h1>\n");
+    for module in modules {
+        let page = page_name(&module.name);
+        body.push_str(&format!("<h2><a href=\"{}\">{}</a></h2>\n<ul>\n", page, escape(&module.name)));
+        for (name, doc) in module.asts.iter().filter_map(definition) {
+            let summary = doc.and_then(|d| d.lines().next()).map(|l| format!(" - {}", escape(l))).unwrap_or_default();
+            body.push_str(&format!("<li><a href=\"{}#{}\"><code>{}</code></a>{}</li>\n", page, name, name, summary));
+        }
+        body.push_str("</ul>\n");
+    }
+    page("Modules", &body)
+}
+
+fn definition(ast : &Ast) -> Option<(&str, Option<&str>)> {
+    match ast {
+        Ast::Fun(f) => Some((&f.name, f.doc.as_deref())),
+        Ast::Data(d) => Some((&d.name, d.doc.as_deref())),
+        Ast::Error(_) => None,
+    }
+}
+
+fn module_page(module : &Module, links : &HashMap<String, String>, type_of : &impl Fn(&str) -> Option<Type>) -> String {
+    let mut body = format!("<nav><a href=\"index.html\">Modules</a></nav>\n<h1>Module <code>{}</code></h1>\n", escape(&module.name));
+
+    let datas = module.asts.iter().filter_map(|a| match a { Ast::Data(d) => Some(d), _ => None }).collect::<Vec<_>>();
+    if !datas.is_empty() {
+        body.push_str("<h2>Data types</h2>\n");
+        for d in datas {
+            body.push_str(&data(d, links));
+        }
+    }
+
+    let funs = module.asts.iter().filter_map(|a| match a { Ast::Fun(f) => Some(f), _ => None }).collect::<Vec<_>>();
+    if !funs.is_empty() {
+        body.push_str("<h2>Functions</h2>\n");
+        for f in funs {
+            let signature = signature(f, type_of(&f.name));
+            body.push_str(&format!("<section id=\"{}\">\n<h3><code>{}</code></h3>\n{}</section>\n",
+                f.name, link_types(&escape(&signature), links), doc(f.doc.as_deref())));
+        }
+    }
+
+    page(&module.name, &body)
+}
+
+fn data(d : &Data, links : &HashMap<String, String>) -> String {
+    let mut header = format!("data {}", d.name);
+    if !d.type_params.is_empty() {
+        header.push_str(&format!("<{}>", d.type_params.iter().map(|p| p.name.as_str()).collect::<Vec<_>>().join(", ")));
+    }
+
+    let mut constructors = String::from("<ul class=\"constructors\">\n");
+    for c in &d.constructors {
+        let mut text = c.name.clone();
+        if !c.fields.is_empty() {
+            text.push_str(&format!("({})", c.fields.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ")));
+        }
+        constructors.push_str(&format!("<li id=\"{}.{}\"><code>{}</code></li>\n", d.name, c.name, link_types(&escape(&text), links)));
+    }
+    constructors.push_str("</ul>\n");
+
+    format!("<section id=\"{}\">\n<h3><code>{}</code></h3>\n{}{}</section>\n", d.name, escape(&header), doc(d.doc.as_deref()), constructors)
+}
+
+/// The parameter names come from the definition and their types from the
+/// inferred type.  Without one the annotations are all there is.
+fn signature(f : &Fun, ty : Option<Type>) -> String {
+    let (params, ret) = match ty {
+        Some(Type::Fun(_, params, ret)) if params.len() == f.params.len() => (params.into_iter().map(Some).collect(), Some(*ret)),
+        _ => (f.params.iter().map(|p| p.param_type.clone()).collect::<Vec<_>>(), f.return_type.clone()),
+    };
+    let params = f.params.iter().zip(params).map(|(p, t)| match t {
+        Some(t) => format!("{} : {}", p.name, t),
+        None => p.name.clone(),
+    }).collect::<Vec<_>>();
+    match ret {
+        Some(ret) => format!("fun {}({}) -> {}", f.name, params.join(", "), ret),
+        None => format!("fun {}({})", f.name, params.join(", ")),
+    }
+}
+
+/// Paragraphs are separated by blank lines.
+fn doc(doc : Option<&str>) -> String {
+    match doc {
+        Some(doc) => doc.split("\n\n")
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .map(|p| format!("<p>{}</p>\n", escape(p)))
+            .collect(),
+        None => "<p class=\"undocumented\">Undocumented.</p>\n".to_string(),
+    }
+}
+
+/// Links the names of data types in already escaped text.  Type names are
+/// capitalized, so they can't be confused with the escapes.
+fn link_types(text : &str, links : &HashMap<String, String>) -> String {
+    let mut ret = String::new();
+    let mut word = String::new();
+    for c in text.chars().chain(['\0']) {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+        match links.get(&word) {
+            Some(href) => ret.push_str(&format!("<a href=\"{}\">{}</a>", href, word)),
+            None => ret.push_str(&word),
+        }
+        word.clear();
+        if c != '\0' {
+            ret.push(c);
+        }
+    }
+    ret
+}
+
+fn escape(text : &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsing::parser::parse;
+    use crate::parsing::tokenizer::tokenize;
+    use crate::source_map::FileId;
+    use crate::typing::infer::Checker;
+
+    fn site(files : &[(&str, &str)]) -> HashMap<String, String> {
+        let asts = files.iter().map(|(_, text)| parse(tokenize(FileId(0), text).0).0).collect::<Vec<_>>();
+        let mut checker = Checker::new();
+        checker.check(&asts.concat()).unwrap();
+        let modules = files.iter().zip(&asts).map(|((name, _), asts)| Module { name: module_name(name), asts }).collect::<Vec<_>>();
+        generate(&modules, |n| checker.type_of(n)).into_iter().collect()
+    }
+
+    #[test]
+    fn should_name_modules_after_files() {
+        assert_eq!( module_name("./lib/list.dr"), "lib.list" );
+        assert_eq!( module_name("<stdin>"), "stdin" );
+    }
+
+    #[test]
+    fn should_document_definitions() {
+        let output = site(&[
+            ("list.dr", "## A list.\n##\n## Maybe <empty>.\ndata List<a> = Cons(a, List<a>) | Nil\n"),
+            ("main.dr", "## Wraps an item.\nfun single(x) { Cons(x, Nil) }\nfun main() { single(1) }\n"),
+        ]);
+
+        assert_eq!( output.len(), 4 );
+        let index = &output["index.html"];
+        assert!( index.contains("<a href=\"module-list.html#List\"><code>List</code></a> - A list.") );
+        assert!( index.contains("<a href=\"module-main.html#main\"><code>main</code></a></li>") );
+
+        let list = &output["module-list.html"];
+        assert!( list.contains("<h3><code>data List&lt;a&gt;</code></h3>\n<p>A list.</p>\n<p>Maybe &lt;empty&gt;.</p>") );
+        assert!( list.contains("<li id=\"List.Cons\"><code>Cons(a, <a href=\"module-list.html#List\">List</a>&lt;a&gt;)</code></li>") );
+        assert!( list.contains("<li id=\"List.Nil\"><code>Nil</code></li>") );
+
+        let main = &output["module-main.html"];
+        assert!( main.contains("<code>fun single(x : a) -&gt; <a href=\"module-list.html#List\">List</a>&lt;a&gt;</code>") );
+        assert!( main.contains("<p>Wraps an item.</p>") );
+        assert!( main.contains("<code>fun main() -&gt; <a href=\"module-list.html#List\">List</a>&lt;Number&gt;</code></h3>\n<p class=\"undocumented\">") );
+    }
+}
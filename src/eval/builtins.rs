@@ -0,0 +1,183 @@
+
+use crate::source_map::Span;
+use super::value::{Value, Builtin};
+use super::evaluator::RuntimeError;
+
+/// Type signatures of the builtin functions written in the surface type syntax.
+pub const SIGNATURES : &[(&str, &str)] = &[
+    ("add", "(Number, Number) -> Number"),
+    ("sub", "(Number, Number) -> Number"),
+    ("mul", "(Number, Number) -> Number"),
+    ("div", "(Number, Number) -> Number"),
+    ("eq", "(a, a) -> Bool"),
+    ("lt", "(Number, Number) -> Bool"),
+    ("gt", "(Number, Number) -> Bool"),
+    ("not", "Bool -> Bool"),
+    ("and", "(Bool, Bool) -> Bool"),
+    ("or", "(Bool, Bool) -> Bool"),
+    ("concat", "(String, String) -> String"),
+    ("to_string", "a -> String"),
+    ("chars", "String -> [Char]"),
+    ("from_chars", "[Char] -> String"),
+    ("print", "a -> ()"),
+    ("cons", "(a, [a]) -> [a]"),
+    ("append", "([a], [a]) -> [a]"),
+    ("length", "[a] -> Number"),
+];
+
+pub fn builtins() -> Vec<Builtin> {
+    vec![
+        Builtin { name: "add", arity: 2, f: |m, args| arithmetic(m, "add", args, i64::checked_add, |a, b| a + b) },
+        Builtin { name: "sub", arity: 2, f: |m, args| arithmetic(m, "sub", args, i64::checked_sub, |a, b| a - b) },
+        Builtin { name: "mul", arity: 2, f: |m, args| arithmetic(m, "mul", args, i64::checked_mul, |a, b| a * b) },
+        Builtin { name: "div", arity: 2, f: div },
+        Builtin { name: "eq", arity: 2, f: eq },
+        Builtin { name: "lt", arity: 2, f: |m, args| compare(m, "lt", args, |o| o.is_lt()) },
+        Builtin { name: "gt", arity: 2, f: |m, args| compare(m, "gt", args, |o| o.is_gt()) },
+        Builtin { name: "not", arity: 1, f: |m, args| match &args[..] {
+            [Value::Bool(b)] => Ok(Value::Bool(!b)),
+            _ => Err(invalid(m, "not", &args)),
+        } },
+        Builtin { name: "and", arity: 2, f: |m, args| match &args[..] {
+            [Value::Bool(a), Value::Bool(b)] => Ok(Value::Bool(*a && *b)),
+            _ => Err(invalid(m, "and", &args)),
+        } },
+        Builtin { name: "or", arity: 2, f: |m, args| match &args[..] {
+            [Value::Bool(a), Value::Bool(b)] => Ok(Value::Bool(*a || *b)),
+            _ => Err(invalid(m, "or", &args)),
+        } },
+        Builtin { name: "concat", arity: 2, f: |m, args| match &args[..] {
+            [Value::String(a), Value::String(b)] => Ok(Value::String(format!("{}{}", a, b))),
+            _ => Err(invalid(m, "concat", &args)),
+        } },
+        Builtin { name: "to_string", arity: 1, f: |_, args| Ok(Value::String(stringify(&args[0]))) },
+        Builtin { name: "chars", arity: 1, f: |m, args| match &args[..] {
+            [Value::String(s)] => Ok(Value::List(s.chars().map(Value::Char).collect())),
+            _ => Err(invalid(m, "chars", &args)),
+        } },
+        Builtin { name: "from_chars", arity: 1, f: |m, args| match &args[..] {
+            [Value::List(items)] => items.iter().map(|c| match c {
+                Value::Char(c) => Ok(*c),
+                _ => Err(invalid(m, "from_chars", &args)),
+            }).collect::<Result<String, _>>().map(Value::String),
+            _ => Err(invalid(m, "from_chars", &args)),
+        } },
+        Builtin { name: "print", arity: 1, f: |_, args| {
+            println!("{}", stringify(&args[0]));
+            Ok(Value::unit())
+        } },
+        Builtin { name: "cons", arity: 2, f: |m, mut args| match args.pop() {
+            Some(Value::List(mut items)) => {
+                items.insert(0, args.pop().unwrap());
+                Ok(Value::List(items))
+            },
+            Some(x) => Err(invalid(m, "cons", &[args.pop().unwrap(), x])),
+            None => unreachable!(),
+        } },
+        Builtin { name: "append", arity: 2, f: |m, args| match &args[..] {
+            [Value::List(a), Value::List(b)] => Ok(Value::List(a.iter().chain(b.iter()).cloned().collect())),
+            _ => Err(invalid(m, "append", &args)),
+        } },
+        Builtin { name: "length", arity: 1, f: |m, args| match &args[..] {
+            [Value::List(items)] => Ok(Value::Integer(items.len() as i64)),
+            _ => Err(invalid(m, "length", &args)),
+        } },
+    ]
+}
+
+/// Strings are displayed without quotes while everything else uses the normal
+/// value display.
+pub fn stringify(value : &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Char(c) => c.to_string(),
+        v => v.to_string(),
+    }
+}
+
+/// Whether an integer and a float are the same number.  The float is converted
+/// back as well so that the integer isn't rounded (9007199254740993 is not
+/// 9007199254740992.0).
+pub fn same_number(i : i64, f : f64) -> bool {
+    i as f64 == f && f < i64::MAX as f64 && f as i64 == i
+}
+
+fn float(value : &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// Integers stay exact (and overflow is an error) unless one of the arguments
+/// is a float.
+fn arithmetic(m : Span, name : &str, args : Vec<Value>, int : fn(i64, i64) -> Option<i64>, f : fn(f64, f64) -> f64) -> Result<Value, RuntimeError> {
+    match &args[..] {
+        [Value::Integer(a), Value::Integer(b)] => int(*a, *b).map(Value::Integer).ok_or(RuntimeError::IntegerOverflow(m)),
+        [a, b] => match (float(a), float(b)) {
+            (Some(a), Some(b)) => Ok(Value::Number(f(a, b))),
+            _ => Err(invalid(m, name, &args)),
+        },
+        _ => Err(invalid(m, name, &args)),
+    }
+}
+
+/// Integers that divide exactly give an integer, anything else a float.
+fn div(m : Span, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    match &args[..] {
+        [_, Value::Integer(0)] => Err(RuntimeError::DivideByZero(m)),
+        [_, Value::Number(b)] if *b == 0.0 => Err(RuntimeError::DivideByZero(m)),
+        [Value::Integer(a), Value::Integer(b)] if a.checked_rem(*b) == Some(0) => Ok(Value::Integer(a / b)),
+        [Value::Integer(a), Value::Integer(b)] => Ok(Value::Number(*a as f64 / *b as f64)),
+        _ => arithmetic(m, "div", args, i64::checked_div, |a, b| a / b),
+    }
+}
+
+fn compare(m : Span, name : &str, args : Vec<Value>, f : fn(std::cmp::Ordering) -> bool) -> Result<Value, RuntimeError> {
+    let ordering = match &args[..] {
+        [Value::Integer(a), Value::Integer(b)] => Some(a.cmp(b)),
+        [a, b] => match (float(a), float(b)) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => return Err(invalid(m, name, &args)),
+        },
+        _ => return Err(invalid(m, name, &args)),
+    };
+    Ok(Value::Bool(ordering.is_some_and(f)))
+}
+
+fn eq(m : Span, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    fn e(m : Span, a : &Value, b : &Value) -> Result<bool, RuntimeError> {
+        fn all(m : Span, a : &[Value], b : &[Value]) -> Result<bool, RuntimeError> {
+            if a.len() != b.len() {
+                return Ok(false);
+            }
+            for (x, y) in a.iter().zip(b.iter()) {
+                if !e(m, x, y)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        match (a, b) {
+            (Value::Integer(x), Value::Integer(y)) => Ok(x == y),
+            (Value::Number(x), Value::Number(y)) => Ok(x == y),
+            (Value::Integer(i), Value::Number(f)) | (Value::Number(f), Value::Integer(i)) => Ok(same_number(*i, *f)),
+            (Value::String(x), Value::String(y)) => Ok(x == y),
+            (Value::Char(x), Value::Char(y)) => Ok(x == y),
+            (Value::Bool(x), Value::Bool(y)) => Ok(x == y),
+            (Value::Tuple(x), Value::Tuple(y)) => all(m, x, y),
+            (Value::List(x), Value::List(y)) => all(m, x, y),
+            (Value::Constructor(x, xs), Value::Constructor(y, ys)) => Ok(x == y && all(m, xs, ys)?),
+            (Value::Closure(_) | Value::Builtin(_), _) | (_, Value::Closure(_) | Value::Builtin(_)) =>
+                Err(RuntimeError::InvalidArgument(m, "functions cannot be compared for equality".into())),
+            _ => Ok(false),
+        }
+    }
+    Ok(Value::Bool(e(m, &args[0], &args[1])?))
+}
+
+fn invalid(m : Span, name : &str, args : &[Value]) -> RuntimeError {
+    let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ");
+    RuntimeError::InvalidArgument(m, format!("{} cannot be applied to ({})", name, args))
+}
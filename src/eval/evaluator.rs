@@ -0,0 +1,399 @@
+
+use std::rc::Rc;
+
+use crate::data::{Ast, Expr, Stmt, StringPart, Pattern};
+use crate::source_map::Span;
+use super::value::{Value, Closure, Env};
+use super::builtins::{stringify, same_number};
+use crate::diagnostic::{Diagnostic, Phase};
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    MissingMain,
+    UnboundVariable(Span, String),
+    UnknownConstructor(Span, String),
+    ConstructorArity { meta : Span, name : String, expected : usize, found : usize },
+    NotCallable(Span, String),
+    ArityMismatch { meta : Span, expected : usize, found : usize },
+    NoMatch(Span, String),
+    InvalidArgument(Span, String),
+    DivideByZero(Span),
+    SyntaxError(Span),
+    IntegerOverflow(Span),
+}
+
+impl RuntimeError {
+    pub fn meta(&self) -> Option<Span> {
+        match self {
+            RuntimeError::MissingMain => None,
+            RuntimeError::UnboundVariable(m, _) => Some(*m),
+            RuntimeError::UnknownConstructor(m, _) => Some(*m),
+            RuntimeError::ConstructorArity { meta, .. } => Some(*meta),
+            RuntimeError::NotCallable(m, _) => Some(*m),
+            RuntimeError::ArityMismatch { meta, .. } => Some(*meta),
+            RuntimeError::NoMatch(m, _) => Some(*m),
+            RuntimeError::InvalidArgument(m, _) => Some(*m),
+            RuntimeError::DivideByZero(m) => Some(*m),
+            RuntimeError::SyntaxError(m) => Some(*m),
+            RuntimeError::IntegerOverflow(m) => Some(*m),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::MissingMain => "E0301",
+            RuntimeError::UnboundVariable(_, _) => "E0302",
+            RuntimeError::UnknownConstructor(_, _) => "E0303",
+            RuntimeError::ConstructorArity { .. } => "E0304",
+            RuntimeError::NotCallable(_, _) => "E0305",
+            RuntimeError::ArityMismatch { .. } => "E0306",
+            RuntimeError::NoMatch(_, _) => "E0307",
+            RuntimeError::InvalidArgument(_, _) => "E0308",
+            RuntimeError::DivideByZero(_) => "E0309",
+            RuntimeError::SyntaxError(_) => "E0310",
+            RuntimeError::IntegerOverflow(_) => "E0311",
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        let ret = Diagnostic::error(Phase::Runtime, self.code(), self.to_string());
+        match self.meta() {
+            Some(meta) => ret.at(meta),
+            None => ret,
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::MissingMain => write!(f, "no main function defined"),
+            RuntimeError::UnboundVariable(_, n) => write!(f, "unbound variable {}", n),
+            RuntimeError::UnknownConstructor(_, n) => write!(f, "unknown constructor {}", n),
+            RuntimeError::ConstructorArity { name, expected, found, .. } =>
+                write!(f, "constructor {} expects {} arguments but was given {}", name, expected, found),
+            RuntimeError::NotCallable(_, v) => write!(f, "{} is not callable", v),
+            RuntimeError::ArityMismatch { expected, found, .. } =>
+                write!(f, "function expects {} arguments but was given {}", expected, found),
+            RuntimeError::NoMatch(_, v) => write!(f, "no match arm matches {}", v),
+            RuntimeError::InvalidArgument(_, message) => write!(f, "{}", message),
+            RuntimeError::DivideByZero(_) => write!(f, "division by zero"),
+            RuntimeError::SyntaxError(_) => write!(f, "cannot evaluate code that failed to parse"),
+            RuntimeError::IntegerOverflow(_) => write!(f, "integer overflow"),
+        }
+    }
+}
+
+/// Loads the definitions and then calls the main function with no arguments.
+pub fn run(asts : &[Ast]) -> Result<Value, RuntimeError> {
+    let env = Env::new();
+    load(&env, asts);
+    let main = asts.iter().find_map(|ast| match ast {
+        Ast::Fun(f) if f.name == "main" => Some(f.meta),
+        _ => None,
+    });
+    match (main, env.lookup("main")) {
+        (Some(meta), Some(f)) => apply(meta, f, vec![]),
+        _ => Err(RuntimeError::MissingMain),
+    }
+}
+
+/// Adds top level functions and data constructors to the global environment.
+pub fn load(env : &Env, asts : &[Ast]) {
+    for ast in asts {
+        match ast {
+            Ast::Fun(f) => {
+                let closure = Closure {
+                    name: Some(f.name.clone()),
+                    params: f.params.iter().map(|p| p.name.clone()).collect(),
+                    body: f.body.clone(),
+                    env: env.global(),
+                };
+                env.define_global(&f.name, Value::Closure(Rc::new(closure)));
+            },
+            Ast::Data(d) => {
+                for c in &d.constructors {
+                    env.define_constructor(&c.name, c.fields.len());
+                }
+            },
+            Ast::Error(_) => { },
+        }
+    }
+}
+
+pub fn eval(env : &Env, expr : &Expr) -> Result<Value, RuntimeError> {
+    match expr {
+        Expr::Integer(_, n) => Ok(Value::Integer(*n)),
+        Expr::Number(_, n) => Ok(Value::Number(*n)),
+        Expr::String(_, s) => Ok(Value::String(s.clone())),
+        Expr::Char(_, c) => Ok(Value::Char(*c)),
+        Expr::Interpolation(_, parts) => {
+            let mut ret = String::new();
+            for part in parts {
+                match part {
+                    StringPart::Text(s) => ret.push_str(s),
+                    StringPart::Expr(e) => ret.push_str(&stringify(&eval(env, e)?)),
+                }
+            }
+            Ok(Value::String(ret))
+        },
+        Expr::Bool(_, b) => Ok(Value::Bool(*b)),
+        Expr::Variable(m, n) => env.lookup(n).ok_or_else(|| RuntimeError::UnboundVariable(*m, n.clone())),
+        Expr::Constructor(m, name, args) => {
+            match env.constructor_arity(name) {
+                Some(arity) if arity == args.len() => { },
+                Some(arity) => return Err(RuntimeError::ConstructorArity { meta: *m, name: name.clone(), expected: arity, found: args.len() }),
+                None => return Err(RuntimeError::UnknownConstructor(*m, name.clone())),
+            }
+            let args = eval_all(env, args)?;
+            Ok(Value::Constructor(name.clone(), args))
+        },
+        Expr::Tuple(_, items) => Ok(Value::Tuple(eval_all(env, items)?)),
+        Expr::List(_, items) => Ok(Value::List(eval_all(env, items)?)),
+        Expr::Call(m, f, args) => {
+            let f = eval(env, f)?;
+            let args = eval_all(env, args)?;
+            apply(*m, f, args)
+        },
+        Expr::Block(_, stmts, result) => {
+            let mut env = env.clone();
+            for stmt in stmts {
+                match stmt {
+                    Stmt::Let { name, value, .. } => {
+                        let value = eval(&env, value)?;
+                        env = env.extend(name.clone(), value);
+                    },
+                    Stmt::Expr(e) => { eval(&env, e)?; },
+                }
+            }
+            match result {
+                Some(e) => eval(&env, e),
+                None => Ok(Value::unit()),
+            }
+        },
+        Expr::Match(m, target, arms) => {
+            let target = eval(env, target)?;
+            for arm in arms {
+                let mut bindings = vec![];
+                if pattern_match(&arm.pattern, &target, &mut bindings) {
+                    let mut env = env.clone();
+                    for (name, value) in bindings {
+                        env = env.extend(name, value);
+                    }
+                    return eval(&env, &arm.body);
+                }
+            }
+            Err(RuntimeError::NoMatch(*m, target.to_string()))
+        },
+        Expr::Lambda(_, params, _, body) => {
+            let closure = Closure {
+                name: None,
+                params: params.iter().map(|p| p.name.clone()).collect(),
+                body: (**body).clone(),
+                env: env.clone(),
+            };
+            Ok(Value::Closure(Rc::new(closure)))
+        },
+        Expr::Error(m) => Err(RuntimeError::SyntaxError(*m)),
+    }
+}
+
+fn eval_all(env : &Env, exprs : &[Expr]) -> Result<Vec<Value>, RuntimeError> {
+    exprs.iter().map(|e| eval(env, e)).collect()
+}
+
+pub fn apply(meta : Span, f : Value, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    match f {
+        Value::Closure(c) => {
+            if c.params.len() != args.len() {
+                return Err(RuntimeError::ArityMismatch { meta, expected: c.params.len(), found: args.len() });
+            }
+            let mut env = c.env.clone();
+            for (name, value) in c.params.iter().zip(args) {
+                env = env.extend(name.clone(), value);
+            }
+            eval(&env, &c.body)
+        },
+        Value::Builtin(b) => {
+            if b.arity != args.len() {
+                return Err(RuntimeError::ArityMismatch { meta, expected: b.arity, found: args.len() });
+            }
+            (b.f)(meta, args)
+        },
+        v => Err(RuntimeError::NotCallable(meta, v.to_string())),
+    }
+}
+
+fn pattern_match(pattern : &Pattern, value : &Value, bindings : &mut Vec<(String, Value)>) -> bool {
+    fn all(patterns : &[Pattern], values : &[Value], bindings : &mut Vec<(String, Value)>) -> bool {
+        patterns.len() == values.len()
+            && patterns.iter().zip(values.iter()).all(|(p, v)| pattern_match(p, v, bindings))
+    }
+    match (pattern, value) {
+        (Pattern::Wildcard(_), _) => true,
+        (Pattern::Variable(_, n), v) => {
+            bindings.push((n.clone(), v.clone()));
+            true
+        },
+        (Pattern::Integer(_, a), Value::Integer(b)) => a == b,
+        (Pattern::Number(_, a), Value::Number(b)) => a == b,
+        (Pattern::Integer(_, i), Value::Number(f)) | (Pattern::Number(_, f), Value::Integer(i)) => same_number(*i, *f),
+        (Pattern::String(_, a), Value::String(b)) => a == b,
+        (Pattern::Char(_, a), Value::Char(b)) => a == b,
+        (Pattern::Bool(_, a), Value::Bool(b)) => a == b,
+        (Pattern::Constructor(_, a, ps), Value::Constructor(b, vs)) => a == b && all(ps, vs, bindings),
+        (Pattern::Tuple(_, ps), Value::Tuple(vs)) => all(ps, vs, bindings),
+        (Pattern::List(_, ps, None), Value::List(vs)) => all(ps, vs, bindings),
+        (Pattern::List(_, ps, Some(rest)), Value::List(vs)) =>
+            ps.len() <= vs.len()
+            && all(ps, &vs[..ps.len()], bindings)
+            && pattern_match(rest, &Value::List(vs[ps.len()..].to_vec()), bindings),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsing::tokenizer::tokenize;
+    use crate::source_map::FileId;
+    use crate::parsing::parser::parse;
+
+    fn r(input : &str) -> Result<Value, RuntimeError> {
+        let asts = parse(tokenize(FileId(0), input).0).0;
+        run(&asts)
+    }
+
+    #[test]
+    fn should_call_functions() {
+        let output = r("fun double(x) { add(x, x) } fun main() { double(2) }").unwrap();
+
+        assert!( matches!( output, Value::Integer(4) ) );
+    }
+
+    #[test]
+    fn should_recurse() {
+        let output = r(r#"
+fun fact(n) {
+    match lt(n, 2) {
+        true => 1,
+        false => mul(n, fact(sub(n, 1))),
+    }
+}
+fun main() { fact(5) }"#).unwrap();
+
+        assert!( matches!( output, Value::Integer(120) ) );
+    }
+
+    #[test]
+    fn should_capture_lexical_scope() {
+        let output = r(r#"
+fun main() {
+    let x = 1;
+    let f = fun () { x };
+    let x = 2;
+    (f(), x)
+}"#).unwrap();
+
+        assert_eq!( output.to_string(), "(1, 2)" );
+    }
+
+    #[test]
+    fn should_match_constructors_and_lists() {
+        let output = r(r#"
+data Option<a> = Some(a) | None
+fun first(xs) {
+    match xs {
+        [] => None,
+        [x | _] => Some(x),
+    }
+}
+fun sum(xs) {
+    match xs {
+        [] => 0,
+        [x | rest] => add(x, sum(rest)),
+    }
+}
+fun main() { (first([1, 2]), first([]), sum([1, 2, 3])) }"#).unwrap();
+
+        assert_eq!( output.to_string(), "(Some(1), None, 6)" );
+    }
+
+    #[test]
+    fn should_interpolate_strings() {
+        let output = r(r#"fun main() { let n = 2; "{n} items: {[n, 3]}, {"done"} \{}" }"#).unwrap();
+
+        assert!( matches!( output, Value::String(s) if s == "2 items: [2, 3], done {}" ) );
+    }
+
+    #[test]
+    fn should_convert_between_chars_and_strings() {
+        let output = r(r#"fun main() { let cs = chars("hé"); (cs, from_chars(cons('\u{1F600}', cs)), to_string('x')) }"#).unwrap();
+
+        assert_eq!( output.to_string(), r#"(['h', 'é'], "😀hé", "x")"# );
+    }
+
+    #[test]
+    fn should_keep_integers_exact() {
+        let output = r("fun main() { 9007199254740993 }").unwrap();
+
+        assert_eq!( output.to_string(), "9007199254740993" );
+    }
+
+    #[test]
+    fn should_divide_integers() {
+        let output = r("fun main() { (div(4, 2), div(3, 2), div(-9223372036854775807, -1)) }").unwrap();
+
+        assert!( matches!( output, Value::Tuple(ref items) if matches!( items[..], [Value::Integer(2), Value::Number(_), Value::Integer(9223372036854775807)] ) ) );
+        assert_eq!( output.to_string(), "(2, 1.5, 9223372036854775807)" );
+    }
+
+    #[test]
+    fn should_show_floats_apart_from_integers() {
+        let output = r("fun main() { (div(4, 2), div(4, 2.0), add(1, 1.0), 1.0, 0.5) }").unwrap();
+
+        assert_eq!( output.to_string(), "(2, 2.0, 2.0, 1.0, 0.5)" );
+    }
+
+    #[test]
+    fn should_match_integers_exactly() {
+        let output = r(r#"
+fun main() {
+    match 9007199254740993 {
+        9007199254740992 => "collided",
+        9007199254740993 => "exact",
+        _ => "none",
+    }
+}"#).unwrap();
+
+        assert!( matches!( output, Value::String(s) if s == "exact" ) );
+    }
+
+    #[test]
+    fn should_report_integer_overflow() {
+        let output = r("fun main() { add(9223372036854775807, 1) }");
+
+        assert!( matches!( output, Err(RuntimeError::IntegerOverflow(Span { start: 13, .. })) ) );
+    }
+
+    #[test]
+    fn should_report_unbound_variable_with_span() {
+        let output = r("fun main() { blah }");
+
+        assert!( matches!( output, Err(RuntimeError::UnboundVariable(Span { start: 13, end: 16, .. }, _)) ) );
+    }
+
+    #[test]
+    fn should_report_no_match() {
+        let output = r("fun main() { match 1 { 2 => 3 } }");
+
+        assert!( matches!( output, Err(RuntimeError::NoMatch(Span { start: 13, .. }, _)) ) );
+    }
+
+    #[test]
+    fn should_report_arity_mismatch() {
+        let output = r("fun f(x) { x } fun main() { f(1, 2) }");
+
+        assert!( matches!( output, Err(RuntimeError::ArityMismatch { expected: 1, found: 2, .. }) ) );
+    }
+}
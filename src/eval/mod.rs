@@ -0,0 +1,3 @@
+pub mod value;
+pub mod builtins;
+pub mod evaluator;
@@ -0,0 +1,147 @@
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::data::Expr;
+use crate::source_map::Span;
+use super::evaluator::RuntimeError;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Integer(i64),
+    Number(f64),
+    String(String),
+    Char(char),
+    Bool(bool),
+    Tuple(Vec<Value>),
+    List(Vec<Value>),
+    Constructor(String, Vec<Value>),
+    Closure(Rc<Closure>),
+    Builtin(Builtin),
+}
+
+impl Value {
+    pub fn unit() -> Value {
+        Value::Tuple(vec![])
+    }
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub name : Option<String>,
+    pub params : Vec<String>,
+    pub body : Expr,
+    pub env : Env,
+}
+
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub name : &'static str,
+    pub arity : usize,
+    pub f : fn(Span, Vec<Value>) -> Result<Value, RuntimeError>,
+}
+
+impl std::fmt::Debug for Builtin {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Builtin({})", self.name)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn comma_list(vs : &[Value]) -> String {
+            vs.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+        }
+        match self {
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Number(n) => write!(f, "{:?}", n),
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Char(c) => write!(f, "{:?}", c),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Tuple(items) => write!(f, "({})", comma_list(items)),
+            Value::List(items) => write!(f, "[{}]", comma_list(items)),
+            Value::Constructor(name, fields) if fields.is_empty() => write!(f, "{}", name),
+            Value::Constructor(name, fields) => write!(f, "{}({})", name, comma_list(fields)),
+            Value::Closure(c) => match &c.name {
+                Some(name) => write!(f, "<fun {}>", name),
+                None => write!(f, "<closure>"),
+            },
+            Value::Builtin(b) => write!(f, "<builtin {}>", b.name),
+        }
+    }
+}
+
+/// Lexical environment.  Locals are an immutable linked list so that closures
+/// capture exactly the bindings visible where they were created.  Globals (top
+/// level functions and constructors) are shared and mutable so that top level
+/// functions can be mutually recursive.
+#[derive(Clone)]
+pub struct Env {
+    globals : Rc<RefCell<HashMap<String, Value>>>,
+    constructors : Rc<RefCell<HashMap<String, usize>>>,
+    locals : Option<Rc<Local>>,
+}
+
+struct Local {
+    name : String,
+    value : Value,
+    next : Option<Rc<Local>>,
+}
+
+impl std::fmt::Debug for Env {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Env")
+    }
+}
+
+impl Env {
+    pub fn new() -> Env {
+        let env = Env {
+            globals: Rc::new(RefCell::new(HashMap::new())),
+            constructors: Rc::new(RefCell::new(HashMap::new())),
+            locals: None,
+        };
+        for b in super::builtins::builtins() {
+            env.define_global(b.name, Value::Builtin(b));
+        }
+        env
+    }
+
+    pub fn lookup(&self, name : &str) -> Option<Value> {
+        let mut current = &self.locals;
+        while let Some(local) = current {
+            if local.name == name {
+                return Some(local.value.clone());
+            }
+            current = &local.next;
+        }
+        self.globals.borrow().get(name).cloned()
+    }
+
+    pub fn extend(&self, name : String, value : Value) -> Env {
+        let local = Local { name, value, next: self.locals.clone() };
+        Env {
+            globals: self.globals.clone(),
+            constructors: self.constructors.clone(),
+            locals: Some(Rc::new(local)),
+        }
+    }
+
+    /// Returns an environment with the same globals but no local bindings.
+    pub fn global(&self) -> Env {
+        Env { globals: self.globals.clone(), constructors: self.constructors.clone(), locals: None }
+    }
+
+    pub fn define_global(&self, name : &str, value : Value) {
+        self.globals.borrow_mut().insert(name.to_string(), value);
+    }
+
+    pub fn define_constructor(&self, name : &str, arity : usize) {
+        self.constructors.borrow_mut().insert(name.to_string(), arity);
+    }
+
+    pub fn constructor_arity(&self, name : &str) -> Option<usize> {
+        self.constructors.borrow().get(name).cloned()
+    }
+}
@@ -0,0 +1,400 @@
+
+use crate::parsing::tokenizer::{Token, Lexeme, Trivia};
+
+const INDENT : &str = "    ";
+
+/// Rewrites source into the canonical layout.  The lexemes should come from
+/// source which parses, otherwise brackets may not line up.
+///
+/// Brackets which were split over several lines stay that way with one item per
+/// line, a trailing comma and aligned match arms.  Blocks with statements are
+/// always split.  Everything else is joined onto one line.
+pub fn format(source : &str, lexemes : &[Lexeme]) -> String {
+    let nodes = build(lexemes);
+    let mut printer = Printer { source, out: String::new(), indent: 0, last: None };
+    for entry in top_level(&nodes) {
+        printer.line();
+        printer.entry(&entry, false, 0);
+    }
+    printer.line();
+    printer.out
+}
+
+enum Node {
+    Token(Token),
+    Group(Group),
+    // Trailing comments share their line with the code before them.
+    Comment { text : String, trailing : bool },
+    // At least one blank line in the original.
+    Blank,
+}
+
+struct Group {
+    open : Token,
+    nodes : Vec<Node>,
+    close : Option<Token>,
+    broken : bool,
+}
+
+impl Node {
+    fn is_code(&self) -> bool {
+        matches!(self, Node::Token(_) | Node::Group(_))
+    }
+}
+
+fn closes(open : &Token, close : &Token) -> bool {
+    matches!((open, close),
+        (Token::LParen(_), Token::RParen(_))
+        | (Token::LCurl(_), Token::RCurl(_))
+        | (Token::LSquare(_), Token::RSquare(_))
+        | (Token::LAngle(_), Token::RAngle(_)))
+}
+
+/// Nests the tokens between brackets and notes which brackets were split over
+/// several lines.
+fn build(lexemes : &[Lexeme]) -> Vec<Node> {
+    let mut stack : Vec<Group> = vec![];
+    let mut top = vec![];
+    let mut newlines = 0;
+    let mut started = false;
+
+    fn push(stack : &mut [Group], top : &mut Vec<Node>, node : Node) {
+        match stack.last_mut() {
+            Some(g) => g.nodes.push(node),
+            None => top.push(node),
+        }
+    }
+
+    for lexeme in lexemes {
+        if let Lexeme::Trivia(Trivia::Whitespace(_, w)) = lexeme {
+            let count = w.matches('\n').count();
+            newlines += count;
+            if let (Some(g), true) = (stack.last_mut(), count != 0) {
+                g.broken = true;
+            }
+            continue;
+        }
+
+        if started && newlines > 1 {
+            push(&mut stack, &mut top, Node::Blank);
+        }
+        let trailing = started && newlines == 0;
+        started = true;
+        newlines = 0;
+
+        match lexeme {
+            Lexeme::Trivia(Trivia::Comment(_, text)) => {
+                if let Some(g) = stack.last_mut() {
+                    g.broken = true;
+                }
+                push(&mut stack, &mut top, Node::Comment { text: text.trim_end().to_string(), trailing });
+            },
+            Lexeme::Token(Token::DocComment(_, text)) => {
+                if let Some(g) = stack.last_mut() {
+                    g.broken = true;
+                }
+                push(&mut stack, &mut top, Node::Comment { text: format!("##{}", text.trim_end()), trailing });
+            },
+            Lexeme::Token(t @ (Token::LParen(_) | Token::LCurl(_) | Token::LSquare(_) | Token::LAngle(_))) =>
+                stack.push(Group { open: t.clone(), nodes: vec![], close: None, broken: false }),
+            Lexeme::Token(t) if stack.last().is_some_and(|g| closes(&g.open, t)) => {
+                let mut g = stack.pop().unwrap();
+                g.close = Some(t.clone());
+                // Statements each get their own line.
+                g.broken |= matches!(g.open, Token::LCurl(_))
+                    && g.nodes.iter().any(|n| matches!(n, Node::Token(Token::SemiColon(_))));
+                push(&mut stack, &mut top, Node::Group(g));
+            },
+            Lexeme::Token(t) => push(&mut stack, &mut top, Node::Token(t.clone())),
+            Lexeme::Trivia(Trivia::Whitespace(_, _)) => unreachable!(),
+        }
+    }
+
+    // Unclosed groups are kept as they are.
+    while let Some(g) = stack.pop() {
+        push(&mut stack, &mut top, Node::Group(g));
+    }
+    top
+}
+
+/// One line's worth of a split group (or the top level).
+#[derive(Default)]
+struct Entry<'a> {
+    nodes : Vec<&'a Node>,
+    separator : Option<&'a Token>,
+    // Comments after the separator which belong on this line.
+    trailing : Vec<&'a Node>,
+}
+
+/// Comments and blank lines in front of a definition belong to it.
+fn top_level(nodes : &[Node]) -> Vec<Entry<'_>> {
+    let mut ret : Vec<Entry> = vec![Entry::default()];
+    for node in nodes {
+        let current = ret.last_mut().unwrap();
+        let starts_definition = matches!(node, Node::Token(Token::Fun(_) | Token::Data(_)));
+        if starts_definition && current.nodes.iter().any(|n| n.is_code()) {
+            let keep = current.nodes.iter()
+                .rposition(|n| n.is_code() || matches!(n, Node::Comment { trailing: true, .. }))
+                .map_or(0, |i| i + 1);
+            let moved = current.nodes.split_off(keep);
+            ret.push(Entry { nodes: moved, ..Entry::default() });
+        }
+        ret.last_mut().unwrap().nodes.push(node);
+    }
+    finish(ret)
+}
+
+fn entries(nodes : &[Node], is_separator : fn(&Token) -> bool) -> (Vec<&Node>, Vec<Entry<'_>>) {
+    let mut lead = vec![];
+    let mut ret : Vec<Entry> = vec![Entry::default()];
+    for node in nodes {
+        let count = ret.len();
+        let current = ret.last_mut().unwrap();
+        match node {
+            Node::Token(t) if is_separator(t) => {
+                current.separator = Some(t);
+                ret.push(Entry::default());
+            },
+            Node::Comment { trailing: true, .. } if current.nodes.is_empty() && count == 1 => lead.push(node),
+            Node::Comment { trailing: true, .. } if current.nodes.is_empty() => ret[count - 2].trailing.push(node),
+            Node::Blank if current.nodes.is_empty() && count == 1 => { },
+            _ => current.nodes.push(node),
+        }
+    }
+    (lead, finish(ret))
+}
+
+fn finish(mut entries : Vec<Entry>) -> Vec<Entry> {
+    for e in &mut entries {
+        while let Some(Node::Blank) = e.nodes.last() {
+            e.nodes.pop();
+        }
+    }
+    entries.retain(|e| !e.nodes.is_empty() || e.separator.is_some() || !e.trailing.is_empty());
+    entries
+}
+
+struct Printer<'a> {
+    source : &'a str,
+    out : String,
+    indent : usize,
+    // The last token on the current line, None at the start of a line.
+    last : Option<Token>,
+}
+
+impl<'a> Printer<'a> {
+    fn text(&self, t : &Token) -> &'a str {
+        let span = t.meta();
+        let end = span.end + self.source[span.end..].chars().next().map_or(0, char::len_utf8);
+        &self.source[span.start..end]
+    }
+
+    fn newline(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        self.out.push('\n');
+        self.last = None;
+    }
+
+    /// Moves to the start of a line unless already there.
+    fn line(&mut self) {
+        if self.last.is_some() {
+            self.newline();
+        }
+    }
+
+    fn token(&mut self, t : &Token) {
+        match &self.last {
+            None => self.out.push_str(&INDENT.repeat(self.indent)),
+            Some(prev) if space(prev, t) => self.out.push(' '),
+            Some(_) => { },
+        }
+        self.out.push_str(self.text(t));
+        self.last = Some(t.clone());
+    }
+
+    fn comment(&mut self, text : &str, trailing : bool) {
+        if trailing && self.last.is_some() {
+            self.out.push(' ');
+        }
+        else {
+            self.line();
+            self.out.push_str(&INDENT.repeat(self.indent));
+        }
+        self.out.push_str(text);
+        self.newline();
+    }
+
+    fn node(&mut self, node : &Node) {
+        match node {
+            Node::Token(t) => self.token(t),
+            Node::Comment { text, trailing } => self.comment(text, *trailing),
+            Node::Blank if self.last.is_none() && !self.out.ends_with("\n\n") && !self.out.is_empty() => self.out.push('\n'),
+            Node::Blank => { },
+            Node::Group(g) if g.broken => self.broken(g),
+            Node::Group(g) => {
+                self.token(&g.open);
+                for n in &g.nodes {
+                    self.node(n);
+                }
+                if let Some(close) = &g.close {
+                    self.token(close);
+                }
+            },
+        }
+    }
+
+    /// Writes the entry's code with the match arrow (if any) padded out to the
+    /// given width and a comma after the last of the code if requested.
+    fn entry(&mut self, entry : &Entry, comma : bool, arrow_width : usize) {
+        let last_code = entry.nodes.iter().rposition(|n| n.is_code());
+        for (i, node) in entry.nodes.iter().enumerate() {
+            if let (Node::Token(Token::DLArrow(_)), true) = (node, arrow_width != 0) {
+                let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+                let width = self.out[line_start..].chars().count();
+                self.out.push_str(&" ".repeat(arrow_width.saturating_sub(width)));
+            }
+            self.node(node);
+            if comma && Some(i) == last_code {
+                self.out.push(',');
+            }
+        }
+        if let Some(separator) = entry.separator {
+            self.token(separator);
+        }
+        for node in &entry.trailing {
+            self.node(node);
+        }
+    }
+
+    fn broken(&mut self, g : &Group) {
+        let is_curl = matches!(g.open, Token::LCurl(_));
+        let (lead, entries) = if is_curl {
+            entries(&g.nodes, |t| matches!(t, Token::SemiColon(_) | Token::Comma(_)))
+        }
+        else {
+            entries(&g.nodes, |t| matches!(t, Token::Comma(_)))
+        };
+
+        let at_level = |e : &Entry, f : fn(&Token) -> bool| e.nodes.iter().any(|n| matches!(n, Node::Token(t) if f(t)));
+        let is_match = is_curl && entries.iter().any(|e| at_level(e, |t| matches!(t, Token::DLArrow(_))));
+        let is_list = entries.iter().any(|e| matches!(e.separator, Some(Token::Comma(_))));
+        // List patterns end with '| rest' which can't be followed by a comma.
+        let comma = (is_match || is_list) && !entries.iter().any(|e| at_level(e, |t| matches!(t, Token::OrBar(_))));
+
+        self.token(&g.open);
+        for node in lead {
+            self.node(node);
+        }
+        self.indent += 1;
+        let arrow_width = if is_match { self.arrow_width(&entries) } else { 0 };
+        let last = entries.iter().rposition(|e| e.nodes.iter().any(|n| n.is_code()));
+        for (i, e) in entries.iter().enumerate() {
+            self.line();
+            let needs_comma = comma && Some(i) == last && e.separator.is_none();
+            self.entry(e, needs_comma, arrow_width);
+        }
+        self.indent -= 1;
+        self.line();
+        if let Some(close) = &g.close {
+            self.token(close);
+        }
+    }
+
+    /// The column where the arrows of match arms line up.  Arms whose patterns
+    /// span lines (or have comments) don't take part.
+    fn arrow_width(&self, entries : &[Entry]) -> usize {
+        entries.iter().filter_map(|e| {
+            let arrow = e.nodes.iter().position(|n| matches!(n, Node::Token(Token::DLArrow(_))))?;
+            let mut p = Printer { source: self.source, out: String::new(), indent: self.indent, last: None };
+            for n in &e.nodes[..arrow] {
+                p.node(n);
+            }
+            (!p.out.contains('\n')).then(|| p.out.chars().count())
+        }).max().unwrap_or(0)
+    }
+}
+
+/// Whether there is a space between two tokens on the same line.
+fn space(prev : &Token, next : &Token) -> bool {
+    match (prev, next) {
+        (Token::LParen(_) | Token::LSquare(_) | Token::LAngle(_) | Token::Dot(_), _) => false,
+        (_, Token::RParen(_) | Token::RSquare(_) | Token::RAngle(_) | Token::Comma(_) | Token::SemiColon(_) | Token::Dot(_)) => false,
+        (Token::LCurl(_), Token::RCurl(_)) => false,
+        // Lambdas and matched tuples keep a space, calls and constructors don't.
+        (Token::Fun(_) | Token::Match(_), Token::LParen(_)) => true,
+        (Token::LowerSymbol(_, _), Token::LParen(_)) => false,
+        (Token::UpperSymbol(_, _), Token::LParen(_) | Token::LAngle(_)) => false,
+        (Token::RParen(_) | Token::RCurl(_), Token::LParen(_)) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsing::tokenizer::tokenize_with_trivia;
+    use crate::parsing::parser::parse;
+    use crate::source_map::FileId;
+
+    fn f(input : &str) -> String {
+        let (lexemes, errors) = tokenize_with_trivia(FileId(0), input);
+        assert_eq!( errors.len(), 0 );
+        let output = format(input, &lexemes);
+
+        // Formatting should be stable and shouldn't break anything.
+        let (again, _) = tokenize_with_trivia(FileId(0), &output);
+        assert_eq!( format(&output, &again), output );
+        let (tokens, _) = crate::parsing::tokenizer::tokenize(FileId(0), &output);
+        assert_eq!( parse(tokens).1.len(), 0 );
+
+        output
+    }
+
+    #[test]
+    fn should_indent_blocks() {
+        let output = f("fun   f( x:Number )->Number{let y=add(x,1);   y}\nfun g() {   f(1)   }");
+
+        assert_eq!( output, "fun f(x : Number) -> Number {\n    let y = add(x, 1);\n    y\n}\nfun g() { f(1) }\n" );
+    }
+
+    #[test]
+    fn should_align_match_arms() {
+        let output = f("fun f(x) {\nmatch x {\n[] => 0,\n[a | rest]=>fun (y) { y }(a),\nSome(_)  =>  1\n}\n}");
+
+        assert_eq!( output, "\
+fun f(x) {
+    match x {
+        []         => 0,
+        [a | rest] => fun (y) { y }(a),
+        Some(_)    => 1,
+    }
+}
+" );
+    }
+
+    #[test]
+    fn should_add_trailing_commas_to_split_lists() {
+        let output = f("data Pair<a,b> = Pair(a,\nb)\nfun f() {\n[1,\n  2] }");
+
+        assert_eq!( output, "data Pair<a, b> = Pair(\n    a,\n    b,\n)\nfun f() {\n    [\n        1,\n        2,\n    ]\n}\n" );
+    }
+
+    #[test]
+    fn should_keep_comments_and_blank_lines() {
+        let output = f("# header\n\n\n# about f\nfun f() { # first\n    let x = 1; # one\n\n    # before x\n    x\n}\n# end");
+
+        assert_eq!( output, "\
+# header
+
+# about f
+fun f() { # first
+    let x = 1; # one
+
+    # before x
+    x
+}
+# end
+" );
+    }
+}
@@ -0,0 +1,320 @@
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// Just enough json for the language server protocol.  Objects keep their keys
+/// in insertion order so that output is predictable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(fields : Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn get(&self, key : &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Follows a path of object keys:  'params.textDocument.uri'.
+    pub fn path(&self, path : &str) -> Option<&Json> {
+        path.split('.').try_fold(self, |json, key| json.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s : &str) -> Json {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s : String) -> Json {
+        Json::String(s)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n : usize) -> Json {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(n : f64) -> Json {
+        Json::Number(n)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b : bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write!(f, "{}", quote(s)),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", quote(k), v)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+/// A json string literal for the text.
+pub fn quote(s : &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+type Input<'a> = Peekable<CharIndices<'a>>;
+
+pub fn parse(text : &str) -> Result<Json, String> {
+    let mut input = text.char_indices().peekable();
+    let ret = parse_value(&mut input)?;
+    skip_whitespace(&mut input);
+    match input.next() {
+        Some((i, c)) => Err(format!("unexpected {:?} at {}", c, i)),
+        None => Ok(ret),
+    }
+}
+
+fn skip_whitespace(input : &mut Input) {
+    while let Some((_, c)) = input.peek() {
+        if !c.is_whitespace() {
+            break;
+        }
+        input.next();
+    }
+}
+
+fn expect(input : &mut Input, expected : char) -> Result<(), String> {
+    match input.next() {
+        Some((_, c)) if c == expected => Ok(()),
+        Some((i, c)) => Err(format!("expected {:?} but found {:?} at {}", expected, c, i)),
+        None => Err(format!("expected {:?} but found end of input", expected)),
+    }
+}
+
+fn parse_value(input : &mut Input) -> Result<Json, String> {
+    skip_whitespace(input);
+    match input.peek() {
+        Some((_, '{')) => parse_object(input),
+        Some((_, '[')) => parse_array(input),
+        Some((_, '"')) => Ok(Json::String(parse_string(input)?)),
+        Some((_, 't')) => parse_word(input, "true", Json::Bool(true)),
+        Some((_, 'f')) => parse_word(input, "false", Json::Bool(false)),
+        Some((_, 'n')) => parse_word(input, "null", Json::Null),
+        Some((_, c)) if *c == '-' || c.is_ascii_digit() => parse_number(input),
+        Some((i, c)) => Err(format!("unexpected {:?} at {}", c, i)),
+        None => Err("unexpected end of input".into()),
+    }
+}
+
+fn parse_word(input : &mut Input, word : &str, value : Json) -> Result<Json, String> {
+    for c in word.chars() {
+        expect(input, c)?;
+    }
+    Ok(value)
+}
+
+fn parse_number(input : &mut Input) -> Result<Json, String> {
+    let mut text = String::new();
+    while let Some((_, c)) = input.peek() {
+        if !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            break;
+        }
+        text.push(*c);
+        input.next();
+    }
+    text.parse::<f64>().map(Json::Number).map_err(|_| format!("invalid number {}", text))
+}
+
+fn parse_string(input : &mut Input) -> Result<String, String> {
+    fn hex(input : &mut Input) -> Result<u32, String> {
+        let mut ret = 0;
+        for _ in 0..4 {
+            match input.next().and_then(|(_, c)| c.to_digit(16)) {
+                Some(d) => ret = ret * 16 + d,
+                None => return Err("invalid unicode escape".into()),
+            }
+        }
+        Ok(ret)
+    }
+
+    expect(input, '"')?;
+    let mut ret = String::new();
+    loop {
+        match input.next() {
+            Some((_, '"')) => return Ok(ret),
+            Some((_, '\\')) => match input.next() {
+                Some((_, '"')) => ret.push('"'),
+                Some((_, '\\')) => ret.push('\\'),
+                Some((_, '/')) => ret.push('/'),
+                Some((_, 'b')) => ret.push('\u{8}'),
+                Some((_, 'f')) => ret.push('\u{c}'),
+                Some((_, 'n')) => ret.push('\n'),
+                Some((_, 'r')) => ret.push('\r'),
+                Some((_, 't')) => ret.push('\t'),
+                Some((_, 'u')) => {
+                    let high = hex(input)?;
+                    let code = if (0xD800..0xDC00).contains(&high) {
+                        expect(input, '\\')?;
+                        expect(input, 'u')?;
+                        let low = hex(input)?;
+                        0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+                    }
+                    else {
+                        high
+                    };
+                    ret.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                },
+                Some((i, c)) => return Err(format!("invalid escape {:?} at {}", c, i)),
+                None => return Err("unexpected end of input in string".into()),
+            },
+            Some((_, c)) => ret.push(c),
+            None => return Err("unexpected end of input in string".into()),
+        }
+    }
+}
+
+fn parse_array(input : &mut Input) -> Result<Json, String> {
+    expect(input, '[')?;
+    let mut ret = vec![];
+    skip_whitespace(input);
+    if let Some((_, ']')) = input.peek() {
+        input.next();
+        return Ok(Json::Array(ret));
+    }
+    loop {
+        ret.push(parse_value(input)?);
+        skip_whitespace(input);
+        match input.next() {
+            Some((_, ',')) => { },
+            Some((_, ']')) => return Ok(Json::Array(ret)),
+            Some((i, c)) => return Err(format!("expected ',' or ']' but found {:?} at {}", c, i)),
+            None => return Err("unexpected end of input in array".into()),
+        }
+    }
+}
+
+fn parse_object(input : &mut Input) -> Result<Json, String> {
+    expect(input, '{')?;
+    let mut ret = vec![];
+    skip_whitespace(input);
+    if let Some((_, '}')) = input.peek() {
+        input.next();
+        return Ok(Json::Object(ret));
+    }
+    loop {
+        skip_whitespace(input);
+        let key = parse_string(input)?;
+        skip_whitespace(input);
+        expect(input, ':')?;
+        ret.push((key, parse_value(input)?));
+        skip_whitespace(input);
+        match input.next() {
+            Some((_, ',')) => { },
+            Some((_, '}')) => return Ok(Json::Object(ret)),
+            Some((i, c)) => return Err(format!("expected ',' or '}}' but found {:?} at {}", c, i)),
+            None => return Err("unexpected end of input in object".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_parse_json() {
+        let output = parse(r#" { "a" : [1, -2.5e1, true, null], "b" : { "c" : "x\"\n\u00e9\ud83d\ude00A" } } "#).unwrap();
+
+        assert_eq!( output.path("b.c").and_then(|c| c.as_str()), Some("x\"\né😀A") );
+        assert_eq!( output.get("a").and_then(|a| a.as_array()).map(|a| a.len()), Some(4) );
+        assert_eq!( output.get("a").unwrap().as_array().unwrap()[1], Json::Number(-25.0) );
+    }
+
+    #[test]
+    fn should_write_json() {
+        let input = Json::object(vec![
+            ("id", 1.into()),
+            ("items", Json::Array(vec![Json::Null, 1.5.into(), "a\"b".into()])),
+        ]);
+
+        assert_eq!( input.to_string(), r#"{"id":1,"items":[null,1.5,"a\"b"]}"# );
+    }
+
+    #[test]
+    fn should_reject_invalid_json() {
+        assert!( parse("{\"a\" 1}").is_err() );
+        assert!( parse("[1, 2").is_err() );
+        assert!( parse("1 2").is_err() );
+    }
+}
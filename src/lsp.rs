@@ -0,0 +1,653 @@
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use crate::data::{Ast, Expr, Stmt, StringPart, Pattern};
+use crate::parsing::tokenizer::{self, Token};
+use crate::parsing::parser;
+use crate::parsing::type_parser::Type;
+use crate::typing::infer::Checker;
+use crate::diagnostic::Diagnostic;
+use crate::source_map::{Span, SourceMap, SourceFile, FileId, Location};
+use crate::json::{self, Json};
+
+const TOKEN_TYPES : &[&str] = &["keyword", "variable", "type", "number", "string", "operator"];
+
+const SYMBOL_FUNCTION : usize = 12;
+const SYMBOL_ENUM : usize = 10;
+const SYMBOL_ENUM_MEMBER : usize = 22;
+
+const METHOD_NOT_FOUND : f64 = -32601.0;
+
+/// Serves the language server protocol until the client sends 'exit' or closes
+/// the input.
+pub fn run(input : &mut impl BufRead, output : &mut impl Write) -> std::io::Result<()> {
+    let mut server = Server::new();
+    while let Some(message) = read_message(input)? {
+        for reply in server.handle(&message) {
+            write_message(output, &reply)?;
+        }
+        if server.exit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reads one 'Content-Length' framed message.  Returns None at the end of the input.
+pub fn read_message(input : &mut impl BufRead) -> std::io::Result<Option<Json>> {
+    fn invalid(message : String) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+    }
+
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| invalid("missing Content-Length header".into()))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|e| invalid(e.to_string()))?;
+    json::parse(&body).map(Some).map_err(invalid)
+}
+
+pub fn write_message(output : &mut impl Write, message : &Json) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+pub struct Server {
+    documents : HashMap<String, Document>,
+    exit : bool,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server { documents: HashMap::new(), exit: false }
+    }
+
+    /// Handles one message from the client and returns the messages to send back.
+    pub fn handle(&mut self, message : &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return self.notification(method, params),
+        };
+
+        let result = match method {
+            "initialize" => Some(capabilities()),
+            "shutdown" => Some(Json::Null),
+            "textDocument/hover" => Some(self.with_position(params, hover).unwrap_or(Json::Null)),
+            "textDocument/definition" => Some(self.with_position(params, definition).unwrap_or(Json::Null)),
+            "textDocument/documentSymbol" => Some(self.document(params).map_or(Json::Null, document_symbols)),
+            "textDocument/semanticTokens/full" => Some(self.document(params).map_or(Json::Null, semantic_tokens)),
+            _ => None,
+        };
+
+        let reply = match result {
+            Some(result) => ("result", result),
+            None => ("error", Json::object(vec![
+                ("code", METHOD_NOT_FOUND.into()),
+                ("message", format!("unknown method {}", method).into()),
+            ])),
+        };
+        vec![Json::object(vec![("jsonrpc", "2.0".into()), ("id", id), reply])]
+    }
+
+    fn notification(&mut self, method : &str, params : &Json) -> Vec<Json> {
+        let uri = params.path("textDocument.uri").and_then(|u| u.as_str()).unwrap_or("").to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params.path("textDocument.text").and_then(|t| t.as_str()).unwrap_or("");
+                self.update(uri, text)
+            },
+            "textDocument/didChange" => {
+                // Only full document sync is advertised, so the last change is the whole text.
+                let changes = params.get("contentChanges").and_then(|c| c.as_array()).unwrap_or(&[]);
+                match changes.last().and_then(|c| c.get("text")).and_then(|t| t.as_str()) {
+                    Some(text) => self.update(uri, text),
+                    None => vec![],
+                }
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                vec![publish_diagnostics(&uri, vec![])]
+            },
+            "exit" => {
+                self.exit = true;
+                vec![]
+            },
+            _ => vec![],
+        }
+    }
+
+    fn update(&mut self, uri : String, text : &str) -> Vec<Json> {
+        let document = Document::new(&uri, text);
+        let file = document.file();
+        let diagnostics = document.diagnostics.iter().map(|d| diagnostic(file, &uri, d)).collect();
+        self.documents.insert(uri.clone(), document);
+        vec![publish_diagnostics(&uri, diagnostics)]
+    }
+
+    fn document(&self, params : &Json) -> Option<&Document> {
+        let uri = params.path("textDocument.uri")?.as_str()?;
+        self.documents.get(uri)
+    }
+
+    fn with_position(&self, params : &Json, f : fn(&Document, usize) -> Option<Json>) -> Option<Json> {
+        let document = self.document(params)?;
+        let line = params.path("position.line")?.as_f64()? as usize;
+        let character = params.path("position.character")?.as_f64()? as usize;
+        let offset = offset(document.file(), line, character)?;
+        f(document, offset)
+    }
+}
+
+/// Everything known about the current text of an open document.
+struct Document {
+    uri : String,
+    sources : SourceMap,
+    file : FileId,
+    tokens : Vec<Token>,
+    asts : Vec<Ast>,
+    types : Vec<(Span, Type)>,
+    diagnostics : Vec<Diagnostic>,
+}
+
+impl Document {
+    fn new(uri : &str, text : &str) -> Document {
+        let mut sources = SourceMap::new();
+        let file = sources.add(uri, text);
+
+        // Parsing goes the same way as on the command line so that the errors are
+        // the same.  The tokens are only kept for highlighting.
+        let tokens = tokenizer::tokenize(file, text).0;
+        let (asts, mut diagnostics) = parser::parse_stream(tokenizer::Tokenizer::new(file, text));
+
+        // Type errors in broken code are mostly noise, but the types that were
+        // inferred before the first error are still useful for hover.
+        let mut checker = Checker::new();
+        if let Err(e) = checker.check(&asts) {
+            if diagnostics.is_empty() {
+                diagnostics.push(e.diagnostic());
+            }
+        }
+        let types = checker.expression_types();
+
+        Document { uri: uri.to_string(), sources, file, tokens, asts, types, diagnostics }
+    }
+
+    fn file(&self) -> &SourceFile {
+        self.sources.get(self.file).expect("document file should be in its source map")
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        ("capabilities", Json::object(vec![
+            ("textDocumentSync", 1.into()),
+            ("hoverProvider", true.into()),
+            ("definitionProvider", true.into()),
+            ("documentSymbolProvider", true.into()),
+            ("semanticTokensProvider", Json::object(vec![
+                ("legend", Json::object(vec![
+                    ("tokenTypes", Json::Array(TOKEN_TYPES.iter().map(|t| (*t).into()).collect())),
+                    ("tokenModifiers", Json::Array(vec![])),
+                ])),
+                ("full", true.into()),
+            ])),
+        ])),
+        ("serverInfo", Json::object(vec![("name", "dour".into())])),
+    ])
+}
+
+fn publish_diagnostics(uri : &str, diagnostics : Vec<Json>) -> Json {
+    Json::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        ("params", Json::object(vec![
+            ("uri", uri.into()),
+            ("diagnostics", Json::Array(diagnostics)),
+        ])),
+    ])
+}
+
+fn diagnostic(file : &SourceFile, uri : &str, d : &Diagnostic) -> Json {
+    // Errors without a location (ie end of file) are shown at the end of the document.
+    let span = d.primary.unwrap_or(Span { file: FileId::NONE, start: file.text.len(), end: file.text.len() });
+    let message = std::iter::once(d.message.clone())
+        .chain(d.notes.iter().map(|n| format!("note: {}", n)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let related = d.labels.iter().map(|l| Json::object(vec![
+        ("location", Json::object(vec![("uri", uri.into()), ("range", range(file, l.meta))])),
+        ("message", l.message.clone().into()),
+    ])).collect();
+    Json::object(vec![
+        ("range", range(file, span)),
+        ("severity", 1.into()),
+        ("code", d.code.into()),
+        ("source", "dour".into()),
+        ("message", message.into()),
+        ("relatedInformation", Json::Array(related)),
+    ])
+}
+
+/// LSP positions are zero based lines and utf-16 columns.
+fn position(file : &SourceFile, offset : usize) -> (usize, usize) {
+    let offset = offset.min(file.text.len());
+    let Location { line, .. } = file.location(offset);
+    let start = file.line_start(line).unwrap_or(0);
+    let character = file.text.get(start..offset).map_or(0, |s| s.encode_utf16().count());
+    (line - 1, character)
+}
+
+fn offset(file : &SourceFile, line : usize, character : usize) -> Option<usize> {
+    let start = file.line_start(line + 1)?;
+    let mut units = 0;
+    for (i, c) in file.text[start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(file.text.len())
+}
+
+fn range(file : &SourceFile, span : Span) -> Json {
+    fn p((line, character) : (usize, usize)) -> Json {
+        Json::object(vec![("line", line.into()), ("character", character.into())])
+    }
+
+    // Span ends are inclusive while LSP range ends are exclusive.
+    let end = file.text.get(span.end..).and_then(|s| s.chars().next()).map_or(span.end, |c| span.end + c.len_utf8());
+    Json::object(vec![("start", p(position(file, span.start))), ("end", p(position(file, end)))])
+}
+
+fn contains(span : Span, offset : usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+fn hover(document : &Document, offset : usize) -> Option<Json> {
+    // The innermost expression is the one being pointed at.
+    let (span, t) = document.types.iter()
+        .filter(|(span, _)| contains(*span, offset))
+        .min_by_key(|(span, _)| span.end - span.start)?;
+    Some(Json::object(vec![
+        ("contents", Json::object(vec![
+            ("kind", "markdown".into()),
+            ("value", format!("```dour\n{}\n```", t).into()),
+        ])),
+        ("range", range(document.file(), *span)),
+    ]))
+}
+
+enum Target {
+    Local(Span),
+    Global(String),
+    Constructor(String),
+    Type(String),
+}
+
+fn definition(document : &Document, offset : usize) -> Option<Json> {
+    let target = document.asts.iter().find_map(|ast| match ast {
+        Ast::Fun(f) if contains(f.meta, offset) => {
+            let mut scope = f.params.iter().map(|p| (p.name.clone(), p.meta)).collect::<Vec<_>>();
+            f.params.iter().filter_map(|p| p.param_type.as_ref()).find_map(|t| find_type(t, offset))
+                .or_else(|| f.return_type.as_ref().and_then(|t| find_type(t, offset)))
+                .or_else(|| find_expr(&f.body, offset, &mut scope))
+        },
+        Ast::Data(d) if contains(d.meta, offset) =>
+            d.constructors.iter().flat_map(|c| c.fields.iter()).find_map(|t| find_type(t, offset)),
+        _ => None,
+    })?;
+
+    let span = match target {
+        Target::Local(span) => Some(span),
+        Target::Global(name) => document.asts.iter().find_map(|ast| match ast {
+            Ast::Fun(f) if f.name == name => Some(f.meta),
+            _ => None,
+        }),
+        Target::Constructor(name) => document.asts.iter().find_map(|ast| match ast {
+            Ast::Data(d) => d.constructors.iter().find(|c| c.name == name).map(|c| c.meta),
+            _ => None,
+        }),
+        Target::Type(name) => document.asts.iter().find_map(|ast| match ast {
+            Ast::Data(d) if d.name == name => Some(d.meta),
+            _ => None,
+        }),
+    }?;
+
+    Some(Json::object(vec![("uri", document.uri.clone().into()), ("range", range(document.file(), span))]))
+}
+
+fn find_type(t : &Type, offset : usize) -> Option<Target> {
+    if !contains(t.meta(), offset) {
+        return None;
+    }
+    match t {
+        Type::Concrete(_, n) => Some(Target::Type(n.clone())),
+        Type::Generic(_, n, args) => args.iter().find_map(|a| find_type(a, offset)).or(Some(Target::Type(n.clone()))),
+        Type::Fun(_, params, ret) => params.iter().find_map(|p| find_type(p, offset)).or_else(|| find_type(ret, offset)),
+        Type::Tuple(_, items) => items.iter().find_map(|i| find_type(i, offset)),
+        Type::List(_, item) => find_type(item, offset),
+        Type::Variable(_, _) => None,
+    }
+}
+
+/// Finds what the symbol at the offset refers to.  The scope holds the local
+/// bindings visible at the expression with the innermost last.
+fn find_expr(expr : &Expr, offset : usize, scope : &mut Vec<(String, Span)>) -> Option<Target> {
+    if !contains(expr.meta(), offset) {
+        return None;
+    }
+    match expr {
+        Expr::Variable(_, n) => match scope.iter().rev().find(|(name, _)| name == n) {
+            Some((_, span)) => Some(Target::Local(*span)),
+            None => Some(Target::Global(n.clone())),
+        },
+        Expr::Constructor(m, name, args) => args.iter().find_map(|a| find_expr(a, offset, scope))
+            .or_else(|| (offset < m.start + name.len()).then(|| Target::Constructor(name.clone()))),
+        Expr::Tuple(_, items) | Expr::List(_, items) => items.iter().find_map(|i| find_expr(i, offset, scope)),
+        Expr::Call(_, f, args) => find_expr(f, offset, scope).or_else(|| args.iter().find_map(|a| find_expr(a, offset, scope))),
+        Expr::Block(_, stmts, result) => {
+            let depth = scope.len();
+            let mut ret = None;
+            for stmt in stmts {
+                ret = match stmt {
+                    Stmt::Let { meta, name, let_type, value } => {
+                        let found = let_type.as_ref().and_then(|t| find_type(t, offset))
+                            .or_else(|| find_expr(value, offset, scope));
+                        scope.push((name.clone(), *meta));
+                        found
+                    },
+                    Stmt::Expr(e) => find_expr(e, offset, scope),
+                };
+                if ret.is_some() {
+                    break;
+                }
+            }
+            let ret = ret.or_else(|| result.as_ref().and_then(|r| find_expr(r, offset, scope)));
+            scope.truncate(depth);
+            ret
+        },
+        Expr::Match(_, target, arms) => find_expr(target, offset, scope).or_else(|| arms.iter().find_map(|arm| {
+            let depth = scope.len();
+            let ret = find_pattern(&arm.pattern, offset, scope).or_else(|| find_expr(&arm.body, offset, scope));
+            scope.truncate(depth);
+            ret
+        })),
+        Expr::Lambda(_, params, return_type, body) => {
+            let depth = scope.len();
+            scope.extend(params.iter().map(|p| (p.name.clone(), p.meta)));
+            let ret = params.iter().filter_map(|p| p.param_type.as_ref()).find_map(|t| find_type(t, offset))
+                .or_else(|| return_type.as_ref().and_then(|t| find_type(t, offset)))
+                .or_else(|| find_expr(body, offset, scope));
+            scope.truncate(depth);
+            ret
+        },
+        Expr::Interpolation(_, parts) => parts.iter().find_map(|p| match p {
+            StringPart::Expr(e) => find_expr(e, offset, scope),
+            StringPart::Text(_) => None,
+        }),
+        Expr::Integer(_, _) | Expr::Number(_, _) | Expr::String(_, _) | Expr::Char(_, _) | Expr::Bool(_, _) | Expr::Error(_) => None,
+    }
+}
+
+/// Adds the pattern's variables to the scope while looking for the offset.
+fn find_pattern(pattern : &Pattern, offset : usize, scope : &mut Vec<(String, Span)>) -> Option<Target> {
+    let ret = match pattern {
+        Pattern::Variable(m, n) => {
+            scope.push((n.clone(), *m));
+            contains(*m, offset).then_some(Target::Local(*m))
+        },
+        Pattern::Constructor(m, name, items) => {
+            let found = items.iter().fold(None, |found, p| found.or(find_pattern(p, offset, scope)));
+            found.or_else(|| (contains(*m, offset) && offset < m.start + name.len()).then(|| Target::Constructor(name.clone())))
+        },
+        Pattern::Tuple(_, items) => items.iter().fold(None, |found, p| found.or(find_pattern(p, offset, scope))),
+        Pattern::List(_, items, rest) => {
+            let found = items.iter().fold(None, |found, p| found.or(find_pattern(p, offset, scope)));
+            match rest {
+                Some(rest) => found.or(find_pattern(rest, offset, scope)),
+                None => found,
+            }
+        },
+        Pattern::Integer(_, _) | Pattern::Number(_, _) | Pattern::String(_, _) | Pattern::Char(_, _) | Pattern::Bool(_, _) | Pattern::Wildcard(_) => None,
+    };
+    ret
+}
+
+fn document_symbols(document : &Document) -> Json {
+    let file = document.file();
+    let symbol = |name : &str, kind : usize, span : Span, children : Vec<Json>| Json::object(vec![
+        ("name", name.into()),
+        ("kind", kind.into()),
+        ("range", range(file, span)),
+        ("selectionRange", range(file, span)),
+        ("children", Json::Array(children)),
+    ]);
+
+    Json::Array(document.asts.iter().filter_map(|ast| match ast {
+        Ast::Fun(f) => Some(symbol(&f.name, SYMBOL_FUNCTION, f.meta, vec![])),
+        Ast::Data(d) => {
+            let constructors = d.constructors.iter().map(|c| symbol(&c.name, SYMBOL_ENUM_MEMBER, c.meta, vec![])).collect();
+            Some(symbol(&d.name, SYMBOL_ENUM, d.meta, constructors))
+        },
+        Ast::Error(_) => None,
+    }).collect())
+}
+
+fn semantic_tokens(document : &Document) -> Json {
+    fn kind(t : &Token) -> Option<usize> {
+        let name = match t {
+            t if t.keyword().is_some() => "keyword",
+            Token::LowerSymbol(_, _) => "variable",
+            Token::UpperSymbol(_, _) => "type",
+            Token::Bool(_, _) => "keyword",
+            Token::Integer(_, _) | Token::Float(_, _) => "number",
+            Token::String(_, _) | Token::Char(_, _) | Token::Interpolated(_, _) => "string",
+            Token::SLArrow(_) | Token::SRArrow(_) | Token::DLArrow(_) | Token::DRArrow(_)
+                | Token::Equal(_) | Token::OrBar(_) => "operator",
+            _ => return None,
+        };
+        TOKEN_TYPES.iter().position(|t| *t == name)
+    }
+
+    let file = document.file();
+    let mut data = vec![];
+    let (mut last_line, mut last_start) = (0, 0);
+    for t in &document.tokens {
+        let kind = match kind(t) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let Span { start, end, .. } = t.meta();
+        let (line, character) = position(file, start);
+        // Tokens can't span lines, so a multi-line string only gets its first line.
+        let text = file.text.get(start..).unwrap_or("");
+        let length = text.char_indices()
+            .take_while(|(i, c)| start + i <= end && *c != '\n')
+            .map(|(_, c)| c.len_utf16())
+            .sum::<usize>();
+
+        let delta_start = if line == last_line { character - last_start } else { character };
+        data.extend([line - last_line, delta_start, length, kind, 0]);
+        last_line = line;
+        last_start = character;
+    }
+
+    Json::object(vec![("data", Json::Array(data.into_iter().map(|d| d.into()).collect()))])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Plays the part of an editor:  sends the messages and returns everything
+    /// the server sent back.
+    fn script(messages : Vec<Json>) -> Vec<Json> {
+        let mut input = vec![];
+        for m in &messages {
+            write_message(&mut input, m).unwrap();
+        }
+        let mut output = vec![];
+        run(&mut std::io::Cursor::new(input), &mut output).unwrap();
+
+        let mut output = std::io::Cursor::new(output);
+        let mut ret = vec![];
+        while let Some(m) = read_message(&mut output).unwrap() {
+            ret.push(m);
+        }
+        ret
+    }
+
+    fn request(id : usize, method : &str, params : Json) -> Json {
+        Json::object(vec![("jsonrpc", "2.0".into()), ("id", id.into()), ("method", method.into()), ("params", params)])
+    }
+
+    fn notify(method : &str, params : Json) -> Json {
+        Json::object(vec![("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+    }
+
+    fn open(text : &str) -> Json {
+        notify("textDocument/didOpen", Json::object(vec![
+            ("textDocument", Json::object(vec![
+                ("uri", "file:///a.dour".into()),
+                ("languageId", "dour".into()),
+                ("version", 1.into()),
+                ("text", text.into()),
+            ])),
+        ]))
+    }
+
+    fn at(line : usize, character : usize) -> Json {
+        Json::object(vec![
+            ("textDocument", Json::object(vec![("uri", "file:///a.dour".into())])),
+            ("position", Json::object(vec![("line", line.into()), ("character", character.into())])),
+        ])
+    }
+
+    fn doc() -> Json {
+        Json::object(vec![("textDocument", Json::object(vec![("uri", "file:///a.dour".into())]))])
+    }
+
+    fn reply(output : &[Json], id : usize) -> &Json {
+        output.iter().find(|m| m.get("id") == Some(&Json::from(id))).expect("reply")
+    }
+
+    const TEXT : &str = "data Box = Box(Number)\nfun double(x) {\n    let y = add(x, x);\n    Box(y)\n}\n";
+
+    #[test]
+    fn should_initialize_and_shutdown() {
+        let output = script(vec![
+            request(1, "initialize", Json::object(vec![])),
+            notify("initialized", Json::object(vec![])),
+            request(2, "blah", Json::Null),
+            request(3, "shutdown", Json::Null),
+            notify("exit", Json::Null),
+            request(4, "shutdown", Json::Null),
+        ]);
+
+        assert_eq!( output.len(), 3 );
+        assert_eq!( reply(&output, 1).path("result.capabilities.hoverProvider"), Some(&Json::Bool(true)) );
+        assert_eq!( reply(&output, 2).path("error.code"), Some(&Json::Number(-32601.0)) );
+        assert_eq!( reply(&output, 3).get("result"), Some(&Json::Null) );
+    }
+
+    #[test]
+    fn should_publish_diagnostics_on_change() {
+        let output = script(vec![
+            open("fun main() { @ }"),
+            notify("textDocument/didChange", Json::object(vec![
+                ("textDocument", Json::object(vec![("uri", "file:///a.dour".into()), ("version", 2.into())])),
+                ("contentChanges", Json::Array(vec![Json::object(vec![("text", "fun main() {\n  add(1, true)\n}".into())])])),
+            ])),
+        ]);
+
+        assert_eq!( output.len(), 2 );
+        let first = output[0].path("params.diagnostics").unwrap().as_array().unwrap();
+        assert_eq!( first.len(), 1 );
+        assert_eq!( first[0].get("code"), Some(&Json::from("E0002")) );
+        assert_eq!( first[0].path("range.start.character"), Some(&Json::Number(13.0)) );
+
+        let second = output[1].path("params.diagnostics").unwrap().as_array().unwrap();
+        assert_eq!( second[0].get("message"), Some(&Json::from("expected Number but found Bool")) );
+        assert_eq!( second[0].path("range.start.line"), Some(&Json::Number(1.0)) );
+        assert_eq!( second[0].path("range.end.character"), Some(&Json::Number(13.0)) );
+    }
+
+    #[test]
+    fn should_hover_with_inferred_type() {
+        let output = script(vec![
+            open(TEXT),
+            request(1, "textDocument/hover", at(2, 16)),
+            request(2, "textDocument/hover", at(3, 5)),
+            request(3, "textDocument/hover", at(4, 1)),
+        ]);
+
+        assert_eq!( reply(&output, 1).path("result.contents.value"), Some(&Json::from("```dour\nNumber\n```")) );
+        assert_eq!( reply(&output, 1).path("result.range.start.character"), Some(&Json::Number(16.0)) );
+        assert_eq!( reply(&output, 2).path("result.contents.value"), Some(&Json::from("```dour\nBox\n```")) );
+        assert_eq!( reply(&output, 3).get("result"), Some(&Json::Null) );
+    }
+
+    #[test]
+    fn should_go_to_definition() {
+        let output = script(vec![
+            open(TEXT),
+            request(1, "textDocument/definition", at(3, 8)),
+            request(2, "textDocument/definition", at(3, 4)),
+            request(3, "textDocument/definition", at(0, 17)),
+            request(4, "textDocument/definition", at(2, 16)),
+        ]);
+
+        assert_eq!( reply(&output, 1).path("result.range.start.line"), Some(&Json::Number(2.0)) );
+        assert_eq!( reply(&output, 2).path("result.range.start.character"), Some(&Json::Number(11.0)) );
+        assert_eq!( reply(&output, 3).get("result"), Some(&Json::Null) );
+        assert_eq!( reply(&output, 4).path("result.range.start.character"), Some(&Json::Number(11.0)) );
+        assert_eq!( reply(&output, 4).path("result.range.end.character"), Some(&Json::Number(12.0)) );
+    }
+
+    #[test]
+    fn should_list_document_symbols() {
+        let output = script(vec![open(TEXT), request(1, "textDocument/documentSymbol", doc())]);
+        let symbols = reply(&output, 1).get("result").unwrap().as_array().unwrap();
+
+        assert_eq!( symbols.len(), 2 );
+        assert_eq!( symbols[0].get("name"), Some(&Json::from("Box")) );
+        assert_eq!( symbols[0].path("children").unwrap().as_array().unwrap()[0].get("name"), Some(&Json::from("Box")) );
+        assert_eq!( symbols[1].get("name"), Some(&Json::from("double")) );
+        assert_eq!( symbols[1].get("kind"), Some(&Json::from(SYMBOL_FUNCTION)) );
+    }
+
+    #[test]
+    fn should_encode_semantic_tokens() {
+        let output = script(vec![open("fun f(x) {\n  \"é\" }"), request(1, "textDocument/semanticTokens/full", doc())]);
+        let data = reply(&output, 1).path("result.data").unwrap().as_array().unwrap()
+            .iter().map(|d| d.as_f64().unwrap() as usize).collect::<Vec<_>>();
+
+        assert_eq!( data, vec![
+            0, 0, 3, 0, 0,
+            0, 4, 1, 1, 0,
+            0, 2, 1, 1, 0,
+            1, 2, 3, 4, 0,
+        ] );
+    }
+}
@@ -4,21 +4,299 @@ extern crate array_pattern;
 
 mod data;
 mod parsing;
+mod eval;
+mod typing;
+mod repl;
+mod diagnostic;
+mod source_map;
+mod json;
+mod lsp;
+mod formatter;
+mod doc;
+
+use std::io::Read;
+use std::process::exit;
+
+use data::Ast;
+use parsing::tokenizer::Tokenizer;
+use source_map::{Span, SourceMap, FileId};
+use diagnostic::{Diagnostic, Phase};
+
+const EXIT_USAGE : i32 = 1;
+const EXIT_TOKENIZE : i32 = 2;
+const EXIT_PARSE : i32 = 3;
+const EXIT_TYPE : i32 = 4;
+const EXIT_RUNTIME : i32 = 5;
+const EXIT_UNFORMATTED : i32 = 6;
+
+const USAGE : &str = "\
+usage: dour [--error-format classic|short|json] [--check] [--out <dir>] <command> [file ...]
+
+commands:
+    tokens    print the tokens of each file
+    parse     print the syntax tree of each file
+    cst       print the concrete syntax tree (every token and comment) of each file
+    check     report errors (including type errors) without running anything
+    run       run the main function
+    fmt       rewrite each file in the canonical layout
+    doc       write html documentation for the files, one page per file
+    repl      start an interactive session (takes no files)
+    lsp       start a language server over stdin/stdout (takes no files)
+
+options:
+    --error-format    'classic' shows the source line (default), 'short' prints
+                      one line per error and 'json' prints one object per error
+    --check           with fmt, list the files which aren't formatted instead
+                      of rewriting them
+    --out             with doc, the directory to write to (default 'doc')
+
+Use '-' as a file name to read from stdin.
+
+exit codes:
+    1    usage or file error
+    2    tokenization error
+    3    parse error
+    4    type error
+    5    runtime error
+    6    file is not formatted (fmt --check)";
+
+#[derive(Clone, Copy)]
+enum ErrorFormat {
+    Classic,
+    Short,
+    Json,
+}
 
 fn main() {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
 
-    let input = "";
+    let format = match args.iter().position(|a| a == "--error-format") {
+        Some(i) if i + 1 < args.len() => {
+            let format = match args[i + 1].as_str() {
+                "classic" => ErrorFormat::Classic,
+                "short" => ErrorFormat::Short,
+                "json" => ErrorFormat::Json,
+                _ => usage(),
+            };
+            args.drain(i..i + 2);
+            format
+        },
+        Some(_) => usage(),
+        None => ErrorFormat::Classic,
+    };
 
-    let token_result = parsing::tokenizer::tokenize(input);
-    let tokens = match token_result {
-        Ok(t) => t,
-        Err(e) => {
-            println!("{}", e);
+    let check_only = match args.iter().position(|a| a == "--check") {
+        Some(i) => {
+            args.remove(i);
+            true
+        },
+        None => false,
+    };
+
+    let out = match args.iter().position(|a| a == "--out") {
+        Some(i) if i + 1 < args.len() => {
+            let out = args[i + 1].clone();
+            args.drain(i..i + 2);
+            Some(out)
+        },
+        Some(_) => usage(),
+        None => None,
+    };
+
+    let (command, files) = match args.split_first() {
+        Some((c, _)) if c == "-h" || c == "--help" => {
+            println!("{}", USAGE);
             return;
         },
+        Some((c, files)) if c == "repl" && files.is_empty() => {
+            repl::Repl::new().run();
+            return;
+        },
+        Some((c, files)) if c == "lsp" && files.is_empty() => {
+            if let Err(e) = lsp::run(&mut std::io::stdin().lock(), &mut std::io::stdout().lock()) {
+                eprintln!("Language server failed: {}", e);
+                exit(EXIT_USAGE);
+            }
+            return;
+        },
+        Some((c, files)) if !files.is_empty() => (c.as_str(), files),
+        _ => usage(),
     };
+    if (check_only && command != "fmt") || (out.is_some() && command != "doc") {
+        usage();
+    }
+
+    let mut sources = SourceMap::new();
+    let files = files.iter().map(|f| read_source(&mut sources, f)).collect::<Vec<_>>();
+
+    match command {
+        "tokens" => {
+            // Invalid tokens are still printed so that they can be seen in context.
+            let mut failed = false;
+            for file in &files {
+                let (tokens, errors) = parsing::tokenizer::tokenize(*file, sources.text(*file));
+                for t in tokens {
+                    let Span { start, end, .. } = t.meta();
+                    println!("{}: {start}..{end} {t}", sources.name(*file));
+                }
+                for e in &errors {
+                    report(&sources, e, format);
+                }
+                failed |= !errors.is_empty();
+            }
+            if failed {
+                exit(EXIT_TOKENIZE);
+            }
+        },
+        "cst" => {
+            // Like tokens, the tree is printed even when there are errors.
+            let mut errors = vec![];
+            for file in &files {
+                let (tree, e) = parsing::cst::parse(*file, sources.text(*file));
+                print!("{}", tree);
+                errors.extend(e);
+            }
+            if !errors.is_empty() {
+                fail(&sources, errors, format);
+            }
+        },
+        "parse" => {
+            for file in &files {
+                println!("{:#?}", parse(&sources, *file, format));
+            }
+        },
+        "check" => {
+            let asts = files.iter().flat_map(|f| parse(&sources, *f, format)).collect::<Vec<_>>();
+            check(&sources, &asts, format);
+        },
+        "run" => {
+            let asts = files.iter().flat_map(|f| parse(&sources, *f, format)).collect::<Vec<_>>();
+            check(&sources, &asts, format);
+            match eval::evaluator::run(&asts) {
+                Ok(eval::value::Value::Tuple(items)) if items.is_empty() => { },
+                Ok(v) => println!("{}", v),
+                Err(e) => fail(&sources, vec![e.diagnostic()], format),
+            }
+        },
+        "fmt" => {
+            let mut unformatted = false;
+            for file in &files {
+                let text = sources.text(*file);
+                let (lexemes, errors) = parsing::tokenizer::tokenize_with_trivia(*file, text);
+                if !errors.is_empty() {
+                    fail(&sources, errors, format);
+                }
+                // Only code which parses has brackets that can be trusted for layout.
+                parse(&sources, *file, format);
+
+                let formatted = formatter::format(text, &lexemes);
+                let name = sources.name(*file);
+                if check_only {
+                    if formatted != text {
+                        println!("{} is not formatted", name);
+                        unformatted = true;
+                    }
+                }
+                else if name == "<stdin>" {
+                    print!("{}", formatted);
+                }
+                else if formatted != text {
+                    if let Err(e) = std::fs::write(name, formatted) {
+                        eprintln!("Unable to write {}: {}", name, e);
+                        exit(EXIT_USAGE);
+                    }
+                }
+            }
+            if unformatted {
+                exit(EXIT_UNFORMATTED);
+            }
+        },
+        "doc" => {
+            let asts = files.iter().map(|f| parse(&sources, *f, format)).collect::<Vec<_>>();
+            let mut checker = typing::infer::Checker::new();
+            if let Err(e) = checker.check(&asts.concat()) {
+                fail(&sources, vec![e.diagnostic()], format);
+            }
+            let modules = files.iter().zip(&asts)
+                .map(|(f, asts)| doc::Module { name: doc::module_name(sources.name(*f)), asts })
+                .collect::<Vec<_>>();
+
+            let out = std::path::PathBuf::from(out.as_deref().unwrap_or("doc"));
+            let written = std::fs::create_dir_all(&out).and_then(|_| {
+                doc::generate(&modules, |n| checker.type_of(n)).into_iter()
+                    .try_for_each(|(name, contents)| std::fs::write(out.join(name), contents))
+            });
+            if let Err(e) = written {
+                eprintln!("Unable to write documentation to {}: {}", out.display(), e);
+                exit(EXIT_USAGE);
+            }
+        },
+        _ => usage(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(EXIT_USAGE);
+}
+
+fn read_source(sources : &mut SourceMap, name : &str) -> FileId {
+    let result = if name == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map(|_| text)
+    }
+    else {
+        std::fs::read_to_string(name)
+    };
+
+    match result {
+        Ok(text) => sources.add(if name == "-" { "<stdin>" } else { name }, text),
+        Err(e) => {
+            eprintln!("Unable to read {}: {}", name, e);
+            exit(EXIT_USAGE);
+        },
+    }
+}
+
+fn report(sources : &SourceMap, diagnostic : &Diagnostic, format : ErrorFormat) {
+    match (format, diagnostic.file()) {
+        (ErrorFormat::Classic, Some(file)) => eprintln!("{}: {}", sources.name(file), diagnostic.render(sources)),
+        (ErrorFormat::Classic, None) => eprintln!("{}", diagnostic.render(sources)),
+        (ErrorFormat::Short, _) => eprintln!("{}", diagnostic.render_short(sources)),
+        (ErrorFormat::Json, _) => eprintln!("{}", diagnostic.render_json(sources)),
+    }
+}
+
+/// Reports the diagnostics and exits with the code for the phase of the first one.
+fn fail(sources : &SourceMap, diagnostics : Vec<Diagnostic>, format : ErrorFormat) -> ! {
+    for d in &diagnostics {
+        report(sources, d, format);
+    }
+    exit(match diagnostics.first().map(|d| d.phase) {
+        Some(Phase::Tokenize) => EXIT_TOKENIZE,
+        Some(Phase::Parse) => EXIT_PARSE,
+        Some(Phase::Type) => EXIT_TYPE,
+        Some(Phase::Runtime) => EXIT_RUNTIME,
+        None => EXIT_USAGE,
+    });
+}
+
+fn check(sources : &SourceMap, asts : &[Ast], format : ErrorFormat) {
+    if let Err(e) = typing::infer::Checker::new().check(asts) {
+        fail(sources, vec![e.diagnostic()], format);
+    }
+}
 
-    let ast_result = parsing::parser::parse(tokens);
-    
-    println!("Hello, world!");
+/// Tokenizer errors are reported on their own, without the parse errors that
+/// they lead to.
+fn parse(sources : &SourceMap, file : FileId, format : ErrorFormat) -> Vec<Ast> {
+    let (asts, errors) = parsing::parser::parse_stream(Tokenizer::new(file, sources.text(file)));
+    let (tokenize, parse) : (Vec<_>, Vec<_>) = errors.into_iter().partition(|d| d.phase == Phase::Tokenize);
+    if !tokenize.is_empty() {
+        fail(sources, tokenize, format);
+    }
+    if !parse.is_empty() {
+        fail(sources, parse, format);
+    }
+    asts
 }
@@ -0,0 +1,316 @@
+
+use crate::data::{Ast, Expr, Stmt, Param, Pattern};
+use crate::diagnostic::Diagnostic;
+use crate::source_map::{Span, FileId};
+use super::tokenizer::{self, SyntaxToken, Trivia, Token};
+use super::type_parser::Type;
+use super::parser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    File,
+    Fun,
+    Data,
+    TypeParam,
+    Constructor,
+    Param,
+    Type,
+    Let,
+    Literal,
+    Variable,
+    ConstructorExpr,
+    Tuple,
+    List,
+    Call,
+    Block,
+    Match,
+    MatchArm,
+    Lambda,
+    Pattern,
+    /// Code that failed to parse.
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub enum Element {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    pub kind : SyntaxKind,
+    pub children : Vec<Element>,
+}
+
+/// A concrete syntax tree:  every token of the file (including invalid ones)
+/// along with its trivia, grouped by the syntax it belongs to.
+#[derive(Debug, Clone)]
+pub struct SyntaxTree {
+    pub root : SyntaxNode,
+    /// Trivia after the last token.
+    pub end : Vec<Trivia>,
+}
+
+/// Builds the syntax tree for a file along with the diagnostics from
+/// tokenizing and parsing it.  The tree is built even when there are errors.
+pub fn parse(file : FileId, input : &str) -> (SyntaxTree, Vec<Diagnostic>) {
+    let (tokens, end, mut diagnostics) = tokenizer::tokenize_lossless(file, input);
+    let valid = tokens.iter().map(|t| t.token.clone()).filter(|t| !matches!(t, Token::Error(_))).collect();
+    let (asts, errors) = parser::parse(valid);
+    diagnostics.extend(errors);
+    (build(tokens, end, &asts), diagnostics)
+}
+
+/// Nests the tokens inside the spans of the syntax tree.  Tokens that no span
+/// covers belong to the innermost node around them, so nothing is lost even if
+/// the tree is incomplete.
+pub fn build(tokens : Vec<SyntaxToken>, end : Vec<Trivia>, asts : &[Ast]) -> SyntaxTree {
+    let shape = Shape {
+        kind: SyntaxKind::File,
+        start: 0,
+        end: usize::MAX,
+        children: asts.iter().map(ast).collect(),
+    };
+    let mut tokens = tokens.into_iter().peekable();
+    let mut root = fill(&shape, &mut tokens);
+    root.children.extend(tokens.map(Element::Token));
+    SyntaxTree { root, end }
+}
+
+#[cfg(test)]
+impl SyntaxTree {
+    /// The exact text that the tree was built from.
+    pub fn text(&self) -> String {
+        let mut ret = self.root.text();
+        ret.extend(self.end.iter().map(|t| t.text()));
+        ret
+    }
+}
+
+#[cfg(test)]
+impl SyntaxNode {
+    pub fn text(&self) -> String {
+        let mut ret = String::new();
+        for t in self.tokens() {
+            ret.extend(t.leading.iter().map(|l| l.text()));
+            ret.push_str(&t.text);
+            ret.extend(t.trailing.iter().map(|l| l.text()));
+        }
+        ret
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|c| match c {
+            Element::Node(n) => Some(n),
+            Element::Token(_) => None,
+        })
+    }
+}
+
+impl SyntaxNode {
+    /// Every token under the node in order.
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        self.children.iter().flat_map(|c| match c {
+            Element::Node(n) => n.tokens(),
+            Element::Token(t) => vec![t],
+        }).collect()
+    }
+
+    /// From the start of the first token to the end of the last one.
+    pub fn span(&self) -> Option<Span> {
+        let tokens = self.tokens();
+        let first = tokens.first()?.token.meta();
+        let last = tokens.last()?.token.meta();
+        Some(Span { end: last.end, ..first })
+    }
+}
+
+/// Shows the nodes and comments with their spans and the tokens (but not the
+/// whitespace).
+impl std::fmt::Display for SyntaxTree {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn comments(trivia : &[Trivia], indent : &str, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            for t in trivia {
+                if let Trivia::Comment(Span { start, end, .. }, c) = t {
+                    writeln!(f, "{indent}comment {start}..{end} {:?}", c)?;
+                }
+            }
+            Ok(())
+        }
+
+        fn write(node : &SyntaxNode, depth : usize, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let indent = "  ".repeat(depth);
+            match node.span() {
+                Some(Span { start, end, .. }) => writeln!(f, "{indent}{:?} {start}..{end}", node.kind)?,
+                None => writeln!(f, "{indent}{:?}", node.kind)?,
+            }
+            let indent = "  ".repeat(depth + 1);
+            for c in &node.children {
+                match c {
+                    Element::Node(n) => write(n, depth + 1, f)?,
+                    Element::Token(t) => {
+                        comments(&t.leading, &indent, f)?;
+                        writeln!(f, "{indent}{:?}", t.text)?;
+                        comments(&t.trailing, &indent, f)?;
+                    },
+                }
+            }
+            Ok(())
+        }
+
+        write(&self.root, 0, f)?;
+        comments(&self.end, "  ", f)
+    }
+}
+
+/// Where the nodes are, taken from the spans of the ast.
+struct Shape {
+    kind : SyntaxKind,
+    start : usize,
+    end : usize,
+    children : Vec<Shape>,
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<SyntaxToken>>;
+
+fn fill(shape : &Shape, tokens : &mut Tokens) -> SyntaxNode {
+    let mut children = vec![];
+    for child in &shape.children {
+        while let Some(t) = tokens.next_if(|t| t.token.meta().start < child.start) {
+            children.push(Element::Token(t));
+        }
+        children.push(Element::Node(fill(child, tokens)));
+    }
+    while let Some(t) = tokens.next_if(|t| t.token.meta().start <= shape.end) {
+        children.push(Element::Token(t));
+    }
+    SyntaxNode { kind: shape.kind, children }
+}
+
+fn shape(kind : SyntaxKind, span : Span, mut children : Vec<Shape>) -> Shape {
+    children.sort_by_key(|c| c.start);
+    Shape { kind, start: span.start, end: span.end, children }
+}
+
+fn ast(ast : &Ast) -> Shape {
+    match ast {
+        Ast::Fun(f) => {
+            let mut children = f.params.iter().map(param).collect::<Vec<_>>();
+            children.extend(f.return_type.iter().map(typ));
+            children.push(expr(&f.body));
+            shape(SyntaxKind::Fun, f.meta, children)
+        },
+        Ast::Data(d) => {
+            let mut children = d.type_params.iter().map(|p| shape(SyntaxKind::TypeParam, p.meta, vec![])).collect::<Vec<_>>();
+            children.extend(d.constructors.iter().map(|c| shape(SyntaxKind::Constructor, c.meta, c.fields.iter().map(typ).collect())));
+            shape(SyntaxKind::Data, d.meta, children)
+        },
+        Ast::Error(m) => shape(SyntaxKind::Error, *m, vec![]),
+    }
+}
+
+fn param(p : &Param) -> Shape {
+    match &p.param_type {
+        Some(t) => shape(SyntaxKind::Param, Span { end: t.meta().end, ..p.meta }, vec![typ(t)]),
+        None => shape(SyntaxKind::Param, p.meta, vec![]),
+    }
+}
+
+fn typ(t : &Type) -> Shape {
+    let children = match t {
+        Type::Concrete(_, _) | Type::Variable(_, _) => vec![],
+        Type::Generic(_, _, args) => args.iter().map(typ).collect(),
+        Type::Fun(_, params, ret) => params.iter().chain([&**ret]).map(typ).collect(),
+        Type::Tuple(_, items) => items.iter().map(typ).collect(),
+        Type::List(_, item) => vec![typ(item)],
+    };
+    shape(SyntaxKind::Type, t.meta(), children)
+}
+
+fn expr(e : &Expr) -> Shape {
+    let (kind, children) = match e {
+        Expr::Integer(_, _) | Expr::Number(_, _) | Expr::String(_, _) | Expr::Char(_, _) | Expr::Bool(_, _) => (SyntaxKind::Literal, vec![]),
+        // The holes are inside the string's token, so they can't have nodes of
+        // their own.
+        Expr::Interpolation(_, _) => (SyntaxKind::Literal, vec![]),
+        Expr::Variable(_, _) => (SyntaxKind::Variable, vec![]),
+        Expr::Constructor(_, _, args) => (SyntaxKind::ConstructorExpr, args.iter().map(expr).collect()),
+        Expr::Tuple(_, items) => (SyntaxKind::Tuple, items.iter().map(expr).collect()),
+        Expr::List(_, items) => (SyntaxKind::List, items.iter().map(expr).collect()),
+        Expr::Call(_, f, args) => (SyntaxKind::Call, std::iter::once(&**f).chain(args).map(expr).collect()),
+        Expr::Block(_, stmts, result) => {
+            let mut children = stmts.iter().map(|s| match s {
+                Stmt::Let { meta, let_type, value, .. } =>
+                    shape(SyntaxKind::Let, *meta, let_type.iter().map(typ).chain([expr(value)]).collect()),
+                Stmt::Expr(e) => expr(e),
+            }).collect::<Vec<_>>();
+            children.extend(result.iter().map(|r| expr(r)));
+            (SyntaxKind::Block, children)
+        },
+        Expr::Match(_, target, arms) => {
+            let mut children = vec![expr(target)];
+            children.extend(arms.iter().map(|a| shape(SyntaxKind::MatchArm, a.meta, vec![pattern(&a.pattern), expr(&a.body)])));
+            (SyntaxKind::Match, children)
+        },
+        Expr::Lambda(_, params, return_type, body) => {
+            let mut children = params.iter().map(param).collect::<Vec<_>>();
+            children.extend(return_type.iter().map(typ));
+            children.push(expr(body));
+            (SyntaxKind::Lambda, children)
+        },
+        Expr::Error(_) => (SyntaxKind::Error, vec![]),
+    };
+    shape(kind, e.meta(), children)
+}
+
+fn pattern(p : &Pattern) -> Shape {
+    let children = match p {
+        Pattern::Constructor(_, _, items) | Pattern::Tuple(_, items) => items.iter().map(pattern).collect(),
+        Pattern::List(_, items, rest) => items.iter().chain(rest.as_deref()).map(pattern).collect(),
+        _ => vec![],
+    };
+    shape(SyntaxKind::Pattern, p.meta(), children)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INPUT : &str = "# list things\ndata List<a> = Cons(a, List<a>) | Nil;\n\nfun f(x : Number) -> List<Number> {\n    let y = [x]; # one\n    match y {\n        [a | _] => Cons(a, Nil),\n        _ => Nil,\n    }\n}\n# end\n";
+
+    #[test]
+    fn should_reconstruct_text() {
+        let (tree, errors) = parse(FileId(0), INPUT);
+
+        assert_eq!( errors.len(), 0 );
+        assert_eq!( tree.text(), INPUT );
+    }
+
+    #[test]
+    fn should_reconstruct_text_with_errors() {
+        let input = "fun f( { @ }\n\"unterminated";
+        let (tree, errors) = parse(FileId(0), input);
+
+        assert!( !errors.is_empty() );
+        assert_eq!( tree.text(), input );
+    }
+
+    #[test]
+    fn should_nest_nodes() {
+        let (tree, _) = parse(FileId(0), INPUT);
+        let kinds = |n : &SyntaxNode| n.nodes().map(|c| c.kind).collect::<Vec<_>>();
+
+        assert_eq!( kinds(&tree.root), vec![SyntaxKind::Data, SyntaxKind::Fun] );
+        let data = tree.root.nodes().next().unwrap();
+        assert_eq!( kinds(data), vec![SyntaxKind::TypeParam, SyntaxKind::Constructor, SyntaxKind::Constructor] );
+        assert_eq!( data.text(), "# list things\ndata List<a> = Cons(a, List<a>) | Nil;\n" );
+
+        let fun = tree.root.nodes().nth(1).unwrap();
+        assert_eq!( kinds(fun), vec![SyntaxKind::Param, SyntaxKind::Type, SyntaxKind::Block] );
+        let block = fun.nodes().nth(2).unwrap();
+        assert_eq!( kinds(block), vec![SyntaxKind::Let, SyntaxKind::Match] );
+        assert_eq!( block.nodes().next().unwrap().text(), "    let y = [x]; # one\n" );
+        assert_eq!( tree.end.iter().map(|t| t.text()).collect::<String>(), "# end\n" );
+    }
+}
@@ -1,4 +1,4 @@
-
 pub mod tokenizer;
 pub mod parser;
-mod type_parser;
\ No newline at end of file
+pub mod type_parser;
+pub mod cst;
@@ -1,8 +1,964 @@
+use std::collections::VecDeque;
 
-use crate::data::Ast;
-use super::tokenizer::{TMeta, Token};
+use crate::data::{Ast, Fun, Param, Data, TypeParam, Constructor, Expr, Stmt, StringPart, MatchArm, Pattern};
+use super::tokenizer::{Token, Segment, Tokenizer};
+use crate::source_map::Span;
+use super::type_parser::{Type, parse_type};
+use crate::diagnostic::{Diagnostic, Phase};
 
-pub fn parse( input : Vec<Token> ) -> Result<Vec<Ast>, String> {
+/// The remaining tokens along with the errors that parsing has already recovered from.
+/// Tokens are either all there up front or pulled from a stream as the parser
+/// asks for them.
+pub struct Input<'a> {
+    // For a stream these are only the tokens that were put back or looked ahead at.
+    tokens : VecDeque<Token>,
+    stream : Option<Tokenizer<'a>>,
+    // Doc comments are kept out of the way of the grammar until a definition
+    // asks for them.  Those before the next token are dropped when it does.
+    docs : VecDeque<(Span, String)>,
+    // The end of the last token taken.
+    last : Option<usize>,
+    pub errors : Vec<Diagnostic>,
+}
+
+impl<'a> Input<'a> {
+    pub fn new(tokens : Vec<Token>) -> Input<'a> {
+        let mut docs = VecDeque::new();
+        let tokens = tokens.into_iter().filter_map(|t| match t {
+            Token::DocComment(m, text) => {
+                docs.push_back((m, text));
+                None
+            },
+            t => Some(t),
+        }).collect();
+        Input { tokens, stream: None, docs, last: None, errors: vec![] }
+    }
+
+    /// Tokenizer errors end up with the parse errors.
+    pub fn stream(tokenizer : Tokenizer<'a>) -> Input<'a> {
+        Input { tokens: VecDeque::new(), stream: Some(tokenizer), docs: VecDeque::new(), last: None, errors: vec![] }
+    }
+
+    /// Moves errors and doc comments at the front of the stream out of the
+    /// way of the grammar.
+    fn skip(&mut self) {
+        while let Some(stream) = &mut self.stream {
+            match stream.peek() {
+                Some(Err(_) | Ok(Token::DocComment(_, _))) => { },
+                _ => return,
+            }
+            match stream.next() {
+                Some(Err(e)) => self.errors.push(e),
+                Some(Ok(Token::DocComment(m, text))) => self.docs.push_back((m, text)),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    fn pull(&mut self) -> Option<Token> {
+        self.skip();
+        self.stream.as_mut()?.next()?.ok()
+    }
+
+    /// The doc comments between the last token taken and the next one.
+    pub fn take_doc(&mut self) -> Option<String> {
+        let next = self.peek().map_or(usize::MAX, |t| t.meta().start);
+        let last = self.last;
+        let mut lines = vec![];
+        while let Some((m, text)) = self.docs.pop_front_if(|(m, _)| m.start < next) {
+            if last.is_none_or(|l| m.start > l) {
+                lines.push(text.strip_prefix(' ').unwrap_or(&text).trim_end().to_string());
+            }
+        }
+        if lines.is_empty() {
+            None
+        }
+        else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    pub fn peek(&mut self) -> Option<&Token> {
+        if !self.tokens.is_empty() {
+            return self.tokens.front();
+        }
+        self.skip();
+        self.stream.as_mut()?.peek()?.as_ref().ok()
+    }
+
+    pub fn peek_nth(&mut self, n : usize) -> Option<&Token> {
+        while self.tokens.len() <= n {
+            let t = self.pull()?;
+            self.tokens.push_back(t);
+        }
+        self.tokens.get(n)
+    }
+
+    /// Unexpected tokens are put back so that recovery can decide whether they end
+    /// the broken statement or definition.
+    pub fn put_back(&mut self, t : Token) {
+        self.tokens.push_front(t);
+    }
+}
+
+impl<'a> Iterator for Input<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let t = match self.tokens.pop_front() {
+            Some(t) => t,
+            None => self.pull()?,
+        };
+        self.last = Some(t.meta().end);
+        Some(t)
+    }
+}
+
+macro_rules! expect_token {
+    ($input:expr, $expected:expr, $($p:pat $(if $g:expr)? => $e:expr),+ $(,)?) => {
+        match $input.next() {
+            $(Some($p) $(if $g)? => $e,)+
+            Some(t) => {
+                let e = $crate::parsing::parser::unexpected(&t, $expected);
+                $input.put_back(t);
+                return Err(Box::new(e));
+            },
+            None => return Err(Box::new($crate::parsing::parser::unexpected_end($expected))),
+        }
+    };
+}
+pub(crate) use expect_token;
+
+/// Diagnostics are large, so they are boxed to keep the results of the parsing
+/// functions small.
+pub type Parsed<T> = Result<T, Box<Diagnostic>>;
+
+pub fn unexpected(t : &Token, expected : &str) -> Diagnostic {
+    Diagnostic::error(Phase::Parse, "E0101", format!("expected {expected} but found {t}")).at(t.meta())
+}
+
+pub fn unexpected_end(expected : &str) -> Diagnostic {
+    Diagnostic::error(Phase::Parse, "E0102", format!("expected {expected} but encountered end of file"))
+}
+
+/// Parses every top level definition.  Definitions that fail to parse become
+/// Ast::Error and parsing resumes at the next definition, so the returned tree is
+/// as complete as possible and the diagnostics include every error found.
+pub fn parse( input : Vec<Token> ) -> (Vec<Ast>, Vec<Diagnostic>) {
+    parse_input(Input::new(input))
+}
+
+/// Like parse, but tokens are pulled from the tokenizer as they're needed
+/// instead of all being held at once.  Tokenizer errors are returned along
+/// with the parse errors.
+pub fn parse_stream( input : Tokenizer ) -> (Vec<Ast>, Vec<Diagnostic>) {
+    parse_input(Input::stream(input))
+}
+
+fn parse_input( mut input : Input ) -> (Vec<Ast>, Vec<Diagnostic>) {
+    let mut ret = vec![];
+    while let Some(t) = input.peek() {
+        let start = t.meta();
+        match parse_top_level(&mut input) {
+            Ok(ast) => ret.push(ast),
+            Err(e) => {
+                input.errors.push(*e);
+                // Always make progress, even when the error was at the start of a definition.
+                let mut end = start;
+                if input.peek().is_some_and(|t| t.meta().start == start.start) {
+                    input.next();
+                }
+                while input.peek().is_some() && !starts_top_level(&mut input) {
+                    end = input.next().unwrap().meta();
+                }
+                ret.push(Ast::Error(m(start, end)));
+            },
+        }
+    }
+    (ret, input.errors)
+}
+
+/// Parses a single 'let' statement or expression.  Used where input arrives one
+/// statement at a time instead of as whole top level definitions.
+pub fn parse_statement( input : Vec<Token> ) -> Result<Stmt, Vec<Diagnostic>> {
+    let mut input = Input::new(input);
+    let ret = match parse_single_statement(&mut input) {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            input.errors.push(*e);
+            return Err(input.errors);
+        },
+    };
+    match input.next() {
+        Some(t) => input.errors.push(unexpected(&t, "end of input")),
+        None if input.errors.is_empty() => return Ok(ret),
+        None => { },
+    }
+    Err(input.errors)
+}
+
+fn parse_single_statement(input : &mut Input) -> Parsed<Stmt> {
+    if let Some(Token::Let(_)) = input.peek() {
+        return parse_let(input);
+    }
+    let expr = parse_expr(input)?;
+    if let Some(Token::SemiColon(_)) = input.peek() {
+        input.next();
+    }
+    Ok(Stmt::Expr(expr))
+}
+
+fn m(start : Span, end : Span) -> Span {
+    Span { file: start.file, start: start.start, end: end.end }
+}
+
+/// Names are lower case symbols.  A keyword where a name should be gets an error
+/// of its own since it looks like a name.
+fn parse_name(input : &mut Input, expected : &str) -> Parsed<(Span, String)> {
+    if let Some(e) = input.peek().and_then(|t| keyword_as_name(t, expected)) {
+        return Err(Box::new(e));
+    }
+    Ok(expect_token!(input, expected, Token::LowerSymbol(m, n) => (m, n)))
+}
+
+fn keyword_as_name(t : &Token, expected : &str) -> Option<Diagnostic> {
+    let k = t.keyword()?;
+    Some(Diagnostic::error(Phase::Parse, "E0104", format!("expected {expected} but found keyword '{k}'"))
+        .at(t.meta())
+        .fix(t.meta(), format!("write r#{k} to use '{k}' as a name"), format!("r#{k}")))
+}
+
+/// 'fun' followed by a name (as opposed to a lambda) or 'data' followed by a type name.
+fn starts_top_level(input : &mut Input) -> bool {
+    match input.peek() {
+        Some(Token::Fun(_)) => matches!(input.peek_nth(1), Some(Token::LowerSymbol(_, _))),
+        Some(Token::Data(_)) => matches!(input.peek_nth(1), Some(Token::UpperSymbol(_, _))),
+        _ => false,
+    }
+}
+
+/// Skips the rest of a broken statement:  through the next ';' or up to the '}'
+/// that closes the block or the start of the next top level definition.  Brackets
+/// opened along the way are skipped along with their contents.  Returns the meta
+/// of the last token skipped.
+fn skip_statement(input : &mut Input) -> Option<Span> {
+    let mut depth = 0;
+    let mut last = None;
+    loop {
+        if depth == 0 && starts_top_level(input) {
+            return last;
+        }
+        match input.peek() {
+            None => return last,
+            Some(Token::RCurl(_)) if depth == 0 => return last,
+            Some(Token::SemiColon(_)) if depth == 0 => return input.next().map(|t| t.meta()),
+            Some(Token::LCurl(_) | Token::LParen(_) | Token::LSquare(_)) => depth += 1,
+            Some(Token::RCurl(_) | Token::RParen(_) | Token::RSquare(_)) if depth > 0 => depth -= 1,
+            Some(_) => { },
+        }
+        last = input.next().map(|t| t.meta());
+    }
+}
+
+fn parse_top_level(input : &mut Input) -> Parsed<Ast> {
+    if let Some(Token::Fun(_)) = input.peek() {
+        Ok(Ast::Fun(parse_fun(input)?))
+    }
+    else if let Some(Token::Data(_)) = input.peek() {
+        Ok(Ast::Data(parse_data(input)?))
+    }
+    else {
+        match input.next() {
+            Some(t) => Err(Box::new(unexpected(&t, "top level definition"))),
+            None => Err(Box::new(unexpected_end("top level definition"))),
+        }
+    }
+}
+
+fn parse_fun(input : &mut Input) -> Parsed<Fun> {
+    let doc = input.take_doc();
+    let start = expect_token!(input, "'fun'", Token::Fun(m) => m);
+    let (_, name) = parse_name(input, "function name")?;
+    let params = parse_params(input)?;
+    let return_type = parse_return_type(input)?;
+    let body = parse_block(input)?;
+
+    Ok(Fun { meta: m(start, body.meta()), doc, name, params, return_type, body })
+}
+
+fn parse_data(input : &mut Input) -> Parsed<Data> {
+    let doc = input.take_doc();
+    let start = expect_token!(input, "'data'", Token::Data(m) => m);
+    let name = expect_token!(input, "data name", Token::UpperSymbol(_, n) => n);
+    let type_params = parse_type_params(input)?;
+    expect_token!(input, "'='", Token::Equal(_) => ());
+
+    let mut constructors = vec![parse_constructor(input)?];
+    while let Some(Token::OrBar(_)) = input.peek() {
+        input.next();
+        constructors.push(parse_constructor(input)?);
+    }
+
+    let end = match input.peek() {
+        Some(Token::SemiColon(_)) => input.next().unwrap().meta(),
+        _ => constructors.last().unwrap().meta,
+    };
+
+    Ok(Data { meta: m(start, end), doc, name, type_params, constructors })
+}
+
+fn parse_type_params(input : &mut Input) -> Parsed<Vec<TypeParam>> {
+    let mut ret = vec![];
+    if !matches!(input.peek(), Some(Token::LAngle(_))) {
+        return Ok(ret);
+    }
+    input.next();
+    loop {
+        if !ret.is_empty() && matches!(input.peek(), Some(Token::RAngle(_))) {
+            input.next();
+            return Ok(ret);
+        }
+        let (meta, name) = parse_name(input, "type parameter")?;
+        ret.push(TypeParam { meta, name });
+        expect_token!(input, "',' or '>'",
+            Token::Comma(_) => (),
+            Token::RAngle(_) => return Ok(ret),
+        );
+    }
+}
+
+fn parse_constructor(input : &mut Input) -> Parsed<Constructor> {
+    let (start, name) = expect_token!(input, "constructor name", Token::UpperSymbol(m, n) => (m, n));
+    let mut fields = vec![];
+    if !matches!(input.peek(), Some(Token::LParen(_))) {
+        return Ok(Constructor { meta: start, name, fields });
+    }
+    input.next();
+    loop {
+        if let Some(Token::RParen(_)) = input.peek() {
+            let end = input.next().unwrap().meta();
+            return Ok(Constructor { meta: m(start, end), name, fields });
+        }
+        fields.push(parse_type(input)?);
+        expect_token!(input, "',' or ')'",
+            Token::Comma(_) => (),
+            Token::RParen(end) => return Ok(Constructor { meta: m(start, end), name, fields }),
+        );
+    }
+}
+
+fn parse_params(input : &mut Input) -> Parsed<Vec<Param>> {
+    fn param(input : &mut Input) -> Parsed<Param> {
+        let (meta, name) = parse_name(input, "parameter name")?;
+        let param_type = match input.peek() {
+            Some(Token::Colon(_)) => {
+                input.next();
+                Some(parse_type(input)?)
+            },
+            _ => None,
+        };
+        Ok(Param { meta, name, param_type })
+    }
+
+    expect_token!(input, "'('", Token::LParen(_) => ());
+    let mut ret = vec![];
+    loop {
+        if matches!(input.peek(), Some(Token::RParen(_))) {
+            input.next();
+            return Ok(ret);
+        }
+        ret.push(param(input)?);
+        expect_token!(input, "',' or ')'",
+            Token::Comma(_) => (),
+            Token::RParen(_) => return Ok(ret),
+        );
+    }
+}
+
+fn parse_return_type(input : &mut Input) -> Parsed<Option<Type>> {
+    match input.peek() {
+        Some(Token::SLArrow(_)) => {
+            input.next();
+            Ok(Some(parse_type(input)?))
+        },
+        _ => Ok(None),
+    }
+}
+
+fn parse_block(input : &mut Input) -> Parsed<Expr> {
+    fn statement(input : &mut Input) -> Parsed<Result<Stmt, (Expr, Span)>> {
+        if let Some(Token::Let(_)) = input.peek() {
+            return Ok(Ok(parse_let(input)?));
+        }
+        let expr = parse_expr(input)?;
+        Ok(expect_token!(input, "';' or '}'",
+            Token::SemiColon(_) => Ok(Stmt::Expr(expr)),
+            Token::RCurl(end) => Err((expr, end)),
+        ))
+    }
+
+    let start = expect_token!(input, "'{'", Token::LCurl(m) => m);
+    let mut stmts = vec![];
+    loop {
+        let stmt_start = match input.peek() {
+            Some(Token::RCurl(_)) => {
+                let end = input.next().unwrap().meta();
+                return Ok(Expr::Block(m(start, end), stmts, None));
+            },
+            Some(t) => t.meta(),
+            None => {
+                input.errors.push(unexpected_end("'}'"));
+                let end = stmts.last().map_or(start, stmt_meta);
+                return Ok(Expr::Block(m(start, end), stmts, None));
+            },
+        };
+        match statement(input) {
+            Ok(Ok(stmt)) => stmts.push(stmt),
+            Ok(Err((expr, end))) => return Ok(Expr::Block(m(start, end), stmts, Some(Box::new(expr)))),
+            Err(e) => {
+                input.errors.push(*e);
+                let end = skip_statement(input).unwrap_or(stmt_start);
+                stmts.push(Stmt::Expr(Expr::Error(m(stmt_start, end))));
+                // A missing '}' leaves the block open until the next definition.
+                if input.peek().is_none() || starts_top_level(input) {
+                    return Ok(Expr::Block(m(start, end), stmts, None));
+                }
+            },
+        }
+    }
+}
+
+fn stmt_meta(stmt : &Stmt) -> Span {
+    match stmt {
+        Stmt::Let { meta, .. } => *meta,
+        Stmt::Expr(e) => e.meta(),
+    }
+}
+
+fn parse_let(input : &mut Input) -> Parsed<Stmt> {
+    let start = expect_token!(input, "'let'", Token::Let(m) => m);
+    let (_, name) = parse_name(input, "let name")?;
+    let let_type = match input.peek() {
+        Some(Token::Colon(_)) => {
+            input.next();
+            Some(parse_type(input)?)
+        },
+        _ => None,
+    };
+    expect_token!(input, "'='", Token::Equal(_) => ());
+    let value = parse_expr(input)?;
+    let end = expect_token!(input, "';'", Token::SemiColon(m) => m);
+    Ok(Stmt::Let { meta: m(start, end), name, let_type, value })
+}
+
+fn parse_expr(input : &mut Input) -> Parsed<Expr> {
+    let mut expr = parse_primary(input)?;
+    while let Some(Token::LParen(_)) = input.peek() {
+        let (end, args) = parse_list(input, "')'", |t| matches!(t, Token::RParen(_)))?;
+        expr = Expr::Call(m(expr.meta(), end), Box::new(expr), args);
+    }
+    Ok(expr)
+}
+
+fn parse_primary(input : &mut Input) -> Parsed<Expr> {
+    match input.peek() {
+        Some(Token::Match(_)) => return parse_match(input),
+        Some(Token::Fun(_)) => return parse_lambda(input),
+        Some(Token::LCurl(_)) => return parse_block(input),
+        Some(Token::LParen(_)) => {
+            let start = input.peek().unwrap().meta();
+            let (end, mut items) = parse_list(input, "')'", |t| matches!(t, Token::RParen(_)))?;
+            if items.len() == 1 {
+                return Ok(items.pop().unwrap());
+            }
+            return Ok(Expr::Tuple(m(start, end), items));
+        },
+        Some(Token::LSquare(_)) => {
+            let start = input.peek().unwrap().meta();
+            let (end, items) = parse_list(input, "']'", |t| matches!(t, Token::RSquare(_)))?;
+            return Ok(Expr::List(m(start, end), items));
+        },
+        Some(Token::UpperSymbol(_, _)) => {
+            let (start, name) = expect_token!(input, "constructor", Token::UpperSymbol(m, n) => (m, n));
+            if !matches!(input.peek(), Some(Token::LParen(_))) {
+                return Ok(Expr::Constructor(start, name, vec![]));
+            }
+            let (end, args) = parse_list(input, "')'", |t| matches!(t, Token::RParen(_)))?;
+            return Ok(Expr::Constructor(m(start, end), name, args));
+        },
+        Some(Token::Interpolated(_, _)) => return parse_interpolation(input),
+        _ => { },
+    }
+
+    Ok(expect_token!(input, "expression",
+        Token::Integer(m, n) => Expr::Integer(m, n),
+        Token::Float(m, n) => Expr::Number(m, n),
+        Token::String(m, s) => Expr::String(m, s),
+        Token::Char(m, c) => Expr::Char(m, c),
+        Token::Bool(m, b) => Expr::Bool(m, b),
+        Token::LowerSymbol(m, n) => Expr::Variable(m, n),
+    ))
+}
+
+fn parse_interpolation(input : &mut Input) -> Parsed<Expr> {
+    let (meta, segments) = expect_token!(input, "string", Token::Interpolated(m, s) => (m, s));
+    let mut parts = vec![];
+    for segment in segments {
+        match segment {
+            Segment::Text(s) => parts.push(StringPart::Text(s)),
+            Segment::Hole(hole, mut tokens) => {
+                // The closing brace is put back so that errors at the end of the
+                // hole point at it instead of the end of the file.
+                tokens.retain(|t| !matches!(t, Token::Error(_)));
+                tokens.push(Token::RCurl(Span { start: hole.end, ..hole }));
+                let mut inner = Input::new(tokens);
+                let expr = parse_expr(&mut inner);
+                input.errors.append(&mut inner.errors);
+                let expr = expr?;
+                expect_token!(inner, "'}'", Token::RCurl(_) => ());
+                parts.push(StringPart::Expr(expr));
+            },
+        }
+    }
+    Ok(Expr::Interpolation(meta, parts))
+}
+
+fn parse_lambda(input : &mut Input) -> Parsed<Expr> {
+    let start = expect_token!(input, "'fun'", Token::Fun(m) => m);
+    let params = parse_params(input)?;
+    let return_type = parse_return_type(input)?;
+    let body = parse_block(input)?;
+
+    Ok(Expr::Lambda(m(start, body.meta()), params, return_type, Box::new(body)))
+}
+
+fn parse_match(input : &mut Input) -> Parsed<Expr> {
+    let start = expect_token!(input, "'match'", Token::Match(m) => m);
+    let target = parse_expr(input)?;
+    expect_token!(input, "'{'", Token::LCurl(_) => ());
+    let mut arms = vec![];
+    loop {
+        if let Some(Token::RCurl(_)) = input.peek() {
+            let end = input.next().unwrap().meta();
+            return Ok(Expr::Match(m(start, end), Box::new(target), arms));
+        }
+        let pattern = parse_pattern(input)?;
+        expect_token!(input, "'=>'", Token::DLArrow(_) => ());
+        let body = parse_expr(input)?;
+        arms.push(MatchArm { meta: m(pattern.meta(), body.meta()), pattern, body });
+        expect_token!(input, "',' or '}'",
+            Token::Comma(_) => (),
+            Token::RCurl(end) => return Ok(Expr::Match(m(start, end), Box::new(target), arms)),
+        );
+    }
+}
+
+fn parse_pattern(input : &mut Input) -> Parsed<Pattern> {
+    match input.peek() {
+        Some(Token::LParen(_)) => {
+            let start = input.peek().unwrap().meta();
+            let (end, mut items) = parse_pattern_list(input, "')'", |t| matches!(t, Token::RParen(_)))?;
+            if items.len() == 1 {
+                return Ok(items.pop().unwrap());
+            }
+            return Ok(Pattern::Tuple(m(start, end), items));
+        },
+        Some(Token::LSquare(_)) => return parse_list_pattern(input),
+        Some(Token::UpperSymbol(_, _)) => {
+            let (start, name) = expect_token!(input, "constructor", Token::UpperSymbol(m, n) => (m, n));
+            if !matches!(input.peek(), Some(Token::LParen(_))) {
+                return Ok(Pattern::Constructor(start, name, vec![]));
+            }
+            let (end, items) = parse_pattern_list(input, "')'", |t| matches!(t, Token::RParen(_)))?;
+            return Ok(Pattern::Constructor(m(start, end), name, items));
+        },
+        _ => { },
+    }
+
+    if let Some(e) = input.peek().and_then(|t| keyword_as_name(t, "pattern")) {
+        return Err(Box::new(e));
+    }
+    Ok(expect_token!(input, "pattern",
+        Token::Integer(m, n) => Pattern::Integer(m, n),
+        Token::Float(m, n) => Pattern::Number(m, n),
+        Token::String(m, s) => Pattern::String(m, s),
+        Token::Char(m, c) => Pattern::Char(m, c),
+        Token::Bool(m, b) => Pattern::Bool(m, b),
+        Token::LowerSymbol(m, n) if n == "_" => Pattern::Wildcard(m),
+        Token::LowerSymbol(m, n) => Pattern::Variable(m, n),
+    ))
+}
+
+fn parse_list_pattern(input : &mut Input) -> Parsed<Pattern> {
+    let start = expect_token!(input, "'['", Token::LSquare(m) => m);
+    let mut items = vec![];
+    loop {
+        if let Some(Token::RSquare(_)) = input.peek() {
+            let end = input.next().unwrap().meta();
+            return Ok(Pattern::List(m(start, end), items, None));
+        }
+        items.push(parse_pattern(input)?);
+        expect_token!(input, "',', '|' or ']'",
+            Token::Comma(_) => (),
+            Token::RSquare(end) => return Ok(Pattern::List(m(start, end), items, None)),
+            Token::OrBar(_) => {
+                let rest = parse_pattern(input)?;
+                let end = expect_token!(input, "']'", Token::RSquare(m) => m);
+                return Ok(Pattern::List(m(start, end), items, Some(Box::new(rest))));
+            },
+        );
+    }
+}
+
+fn parse_pattern_list(input : &mut Input, closing : &str, close : fn(&Token) -> bool) -> Parsed<(Span, Vec<Pattern>)> {
+    input.next();
+    let mut ret = vec![];
+    loop {
+        // Checking for the end first allows a trailing comma.
+        if input.peek().is_some_and(close) {
+            return Ok((input.next().unwrap().meta(), ret));
+        }
+        ret.push(parse_pattern(input)?);
+        expect_token!(input, &format!("',' or {closing}"),
+            Token::Comma(_) => (),
+            t if close(&t) => return Ok((t.meta(), ret)),
+        );
+    }
+}
+
+// Parses a comma separated list of expressions starting with an opening token and
+// ending with the closing token that 'close' recognizes.  Returns the closing meta.
+fn parse_list(input : &mut Input, closing : &str, close : fn(&Token) -> bool) -> Parsed<(Span, Vec<Expr>)> {
+    input.next();
+    let mut ret = vec![];
+    loop {
+        // Checking for the end first allows a trailing comma.
+        if input.peek().is_some_and(close) {
+            return Ok((input.next().unwrap().meta(), ret));
+        }
+        ret.push(parse_expr(input)?);
+        expect_token!(input, &format!("',' or {closing}"),
+            Token::Comma(_) => (),
+            t if close(&t) => return Ok((t.meta(), ret)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsing::tokenizer::tokenize;
+    use crate::source_map::FileId;
+
+    fn p(input : &str) -> Vec<Ast> {
+        let (asts, errors) = parse(tokenize(FileId(0), input).0);
+        assert_eq!( errors.len(), 0 );
+        asts
+    }
+
+    #[test]
+    fn should_parse_empty_function() {
+        let output = p("fun blah() { }");
+
+        assert_eq!( output.len(), 1 );
+        let f = match &output[0] {
+            Ast::Fun(f) => f,
+            _ => panic!("not fun"),
+        };
+        assert_eq!( f.name, "blah" );
+        assert_eq!( f.params.len(), 0 );
+        assert_eq!( f.meta.start, 0 );
+        assert_eq!( f.meta.end, 13 );
+        assert!( f.return_type.is_none() );
+        assert!( matches!( f.body, Expr::Block(_, ref stmts, None) if stmts.is_empty() ) );
+    }
+
+    #[test]
+    fn should_parse_function_with_types() {
+        let output = p("fun blah(a : Number, b) -> a { b }");
+
+        let f = match &output[0] {
+            Ast::Fun(f) => f,
+            _ => panic!("not fun"),
+        };
+        assert_eq!( f.params.len(), 2 );
+        assert_eq!( f.params[0].name, "a" );
+        assert!( matches!( f.params[0].param_type, Some(Type::Concrete(_, ref n)) if n == "Number" ) );
+        assert_eq!( f.params[1].name, "b" );
+        assert!( f.params[1].param_type.is_none() );
+        assert!( matches!( f.return_type, Some(Type::Variable(_, ref n)) if n == "a" ) );
+        assert!( matches!( f.body, Expr::Block(_, _, Some(ref e)) if matches!(**e, Expr::Variable(_, ref n) if n == "b") ) );
+    }
+
+    #[test]
+    fn should_parse_block_statements() {
+        let output = p("fun blah() { let x : Number = 1; print(x, (x, \"y\"), [true]); x }");
+
+        let f = match &output[0] {
+            Ast::Fun(f) => f,
+            _ => panic!("not fun"),
+        };
+        let (stmts, result) = match &f.body {
+            Expr::Block(_, stmts, Some(result)) => (stmts, result),
+            _ => panic!("not block"),
+        };
+        assert_eq!( stmts.len(), 2 );
+        assert!( matches!( stmts[0], Stmt::Let { ref name, let_type: Some(_), value: Expr::Integer(_, 1), .. } if name == "x" ) );
+
+        let args = match &stmts[1] {
+            Stmt::Expr(Expr::Call(_, _, args)) => args,
+            _ => panic!("not call"),
+        };
+        assert_eq!( args.len(), 3 );
+        assert!( matches!( args[1], Expr::Tuple(_, ref items) if items.len() == 2 ) );
+        assert!( matches!( args[2], Expr::List(_, ref items) if items.len() == 1 ) );
+        assert!( matches!( **result, Expr::Variable(_, _) ) );
+    }
+
+    #[test]
+    fn should_parse_multiple_functions() {
+        let output = p("fun a() { } fun b(x) { x }");
+
+        assert_eq!( output.len(), 2 );
+    }
+
+    #[test]
+    fn should_parse_data() {
+        let output = p("data Shape = Circle(Number) | Rect(Number, Number) | Empty;");
+
+        assert_eq!( output.len(), 1 );
+        let d = match &output[0] {
+            Ast::Data(d) => d,
+            _ => panic!("not data"),
+        };
+        assert_eq!( d.name, "Shape" );
+        assert_eq!( d.meta.start, 0 );
+        assert_eq!( d.meta.end, 58 );
+        assert_eq!( d.type_params.len(), 0 );
+        assert_eq!( d.constructors.len(), 3 );
+        assert_eq!( d.constructors[0].name, "Circle" );
+        assert_eq!( d.constructors[0].fields.len(), 1 );
+        assert_eq!( d.constructors[1].name, "Rect" );
+        assert_eq!( d.constructors[1].fields.len(), 2 );
+        assert_eq!( d.constructors[2].name, "Empty" );
+        assert_eq!( d.constructors[2].fields.len(), 0 );
+    }
+
+    #[test]
+    fn should_parse_data_with_type_params() {
+        let output = p("data Either<a, b> = Left(a) | Right(b) fun f() { Left(Nothing) }");
+
+        assert_eq!( output.len(), 2 );
+        let d = match &output[0] {
+            Ast::Data(d) => d,
+            _ => panic!("not data"),
+        };
+        assert_eq!( d.type_params.len(), 2 );
+        assert_eq!( d.type_params[0].name, "a" );
+        assert_eq!( d.type_params[1].name, "b" );
+        assert!( matches!( d.constructors[1].fields[0], Type::Variable(_, ref n) if n == "b" ) );
+
+        let f = match &output[1] {
+            Ast::Fun(f) => f,
+            _ => panic!("not fun"),
+        };
+        assert!( matches!( f.body, Expr::Block(_, _, Some(ref e)) 
+            if matches!(**e, Expr::Constructor(_, ref n, ref args) if n == "Left" && args.len() == 1) ) );
+    }
+
+    #[test]
+    fn should_parse_match() {
+        let output = p(r#"
+fun f(x) { 
+    match x { 
+        1 => "one",
+        "two" => 2,
+        true => false,
+        _ => x,
+        y => y,
+        Cons(a, Nil) => a,
+        (a, _, -3.5) => a,
+        [] => [],
+        [a, b] => b,
+        [a | rest] => rest,
+    }
+}"#);
+
+        let f = match &output[0] {
+            Ast::Fun(f) => f,
+            _ => panic!("not fun"),
+        };
+        let arms = match &f.body {
+            Expr::Block(_, _, Some(e)) => match &**e {
+                Expr::Match(_, target, arms) if matches!(**target, Expr::Variable(_, _)) => arms,
+                _ => panic!("not match"),
+            },
+            _ => panic!("not block"),
+        };
+        assert_eq!( arms.len(), 10 );
+        assert!( matches!( arms[0].pattern, Pattern::Integer(_, 1) ) );
+        assert!( matches!( arms[1].pattern, Pattern::String(_, ref s) if s == "two" ) );
+        assert!( matches!( arms[2].pattern, Pattern::Bool(_, true) ) );
+        assert!( matches!( arms[3].pattern, Pattern::Wildcard(_) ) );
+        assert!( matches!( arms[4].pattern, Pattern::Variable(_, ref n) if n == "y" ) );
+        assert!( matches!( arms[5].pattern, Pattern::Constructor(_, ref n, ref ps) 
+            if n == "Cons" && matches!(ps[1], Pattern::Constructor(_, _, ref x) if x.is_empty()) ) );
+        assert!( matches!( arms[6].pattern, Pattern::Tuple(_, ref ps) if matches!(ps[2], Pattern::Number(_, n) if n == -3.5) ) );
+        assert!( matches!( arms[7].pattern, Pattern::List(_, ref ps, None) if ps.is_empty() ) );
+        assert!( matches!( arms[8].pattern, Pattern::List(_, ref ps, None) if ps.len() == 2 ) );
+        assert!( matches!( arms[9].pattern, Pattern::List(_, ref ps, Some(_)) if ps.len() == 1 ) );
+    }
+
+    #[test]
+    fn should_parse_lambda() {
+        let output = p("fun f() { let g = fun (x : Number) -> Number { x }; g(1) }");
+
+        let f = match &output[0] {
+            Ast::Fun(f) => f,
+            _ => panic!("not fun"),
+        };
+        let stmts = match &f.body {
+            Expr::Block(_, stmts, Some(_)) => stmts,
+            _ => panic!("not block"),
+        };
+        assert!( matches!( stmts[0], Stmt::Let { value: Expr::Lambda(_, ref ps, Some(_), _), .. } if ps.len() == 1 ) );
+    }
+
+    #[test]
+    fn should_allow_trailing_commas() {
+        let output = p(r#"
+data Pair<a, b,> = Pair(a, b,)
+fun f(x : (Number, [a],), y,) -> Pair<Number, [a],> {
+    match [x, y,] {
+        [a, b,] => (a, b,),
+        _ => f(1, [],),
+    }
+}"#);
+
+        assert_eq!( output.len(), 2 );
+        let f = match &output[1] {
+            Ast::Fun(f) => f,
+            _ => panic!("not fun"),
+        };
+        assert_eq!( f.params.len(), 2 );
+        assert!( matches!( f.params[0].param_type, Some(Type::Tuple(_, ref items)) if items.len() == 2 ) );
+    }
+
+    #[test]
+    fn should_parse_interpolation() {
+        let output = p(r#"fun f(x) { "x is {x}, {g(x)}!" }"#);
+        let parts = match &output[0] {
+            Ast::Fun(Fun { body: Expr::Block(_, _, Some(result)), .. }) => match &**result {
+                Expr::Interpolation(_, parts) => parts,
+                _ => panic!("not interpolation"),
+            },
+            _ => panic!("not fun"),
+        };
+
+        assert_eq!( parts.len(), 5 );
+        assert!( matches!( &parts[0], StringPart::Text(s) if s == "x is " ) );
+        assert!( matches!( &parts[1], StringPart::Expr(Expr::Variable(_, n)) if n == "x" ) );
+        assert!( matches!( &parts[3], StringPart::Expr(Expr::Call(_, _, _)) ) );
+        assert!( matches!( &parts[4], StringPart::Text(s) if s == "!" ) );
+    }
+
+    #[test]
+    fn should_report_errors_in_holes() {
+        let (_, errors) = parse(tokenize(FileId(0), r#"fun f() { "a {1 2} b" }"#).0);
+        assert_eq!( errors[0].message, "expected '}' but found integer '2'" );
+        assert!( matches!( errors[0].primary, Some(Span { start: 16, end: 16, .. }) ) );
+
+        let (_, errors) = parse(tokenize(FileId(0), r#"fun f() { "a {} b" }"#).0);
+        assert_eq!( errors[0].message, "expected expression but found '}'" );
+        assert!( matches!( errors[0].primary, Some(Span { start: 14, end: 14, .. }) ) );
+    }
+
+    #[test]
+    fn should_attach_doc_comments() {
+        let output = p("## Some items.\n##\n##   Maybe none.\ndata L = C(Number) | N\n\nfun f() {\n    ## inside\n    1\n}\n## Returns two.\nfun g() { 2 }");
+
+        assert!( matches!( &output[0], Ast::Data(Data { doc: Some(d), .. }) if d == "Some items.\n\n  Maybe none." ) );
+        assert!( matches!( &output[1], Ast::Fun(Fun { doc: None, .. }) ) );
+        assert!( matches!( &output[2], Ast::Fun(Fun { doc: Some(d), .. }) if d == "Returns two." ) );
+    }
+
+    #[test]
+    fn should_parse_streamed_tokens() {
+        let input = "## Some items.\ndata L = C(Number) | N\nfun f(x) { match x { C(y) => y, N => 0 } }\nfun g() { \"{f(N)}\" }";
+        let (output, errors) = parse_stream(Tokenizer::new(FileId(0), input));
+
+        assert_eq!( errors.len(), 0 );
+        assert_eq!( format!("{:?}", output), format!("{:?}", p(input)) );
+
+        let (output, errors) = parse_stream(Tokenizer::new(FileId(0), "fun f(@x) { 1 } ) fun g() { 3 }"));
+        assert!( matches!( output[..], [Ast::Fun(_), Ast::Error(_), Ast::Fun(_)] ) );
+        assert_eq!( errors.iter().map(|e| e.code).collect::<Vec<_>>(), ["E0002", "E0101"] );
+    }
+
+    #[test]
+    fn should_report_keywords_used_as_names() {
+        let t = |input : &str| parse(tokenize(FileId(0), input).0).1;
+
+        for input in ["fun f(match) { 1 }", "fun data() { 1 }", "fun f() { let if = 1; 2 }", "fun f(x) { match x { import => 1 } }", "data T<let> = T"] {
+            let errors = t(input);
+            assert_eq!( errors[0].code, "E0104", "{}", input );
+            let fix = errors[0].fix.as_ref().unwrap();
+            assert_eq!( fix.replacement, format!("r#{}", &input[fix.meta.start..=fix.meta.end]) );
+        }
+
+        let errors = t("fun f(x) { let y = x; 1 }\nfun g() { let if = 1; 2 }");
+        assert_eq!( errors[0].message, "expected let name but found keyword 'if'" );
+        assert!( matches!( errors[0].primary, Some(Span { start: 40, end: 41, .. }) ) );
+
+        assert_eq!( t("fun r#data(r#match) { let r#if = r#match; r#if }").len(), 0 );
+    }
+
+    #[test]
+    fn should_fail_on_missing_paren() {
+        let (_, errors) = parse(tokenize(FileId(0), "fun blah( { }").0);
+
+        assert!( !errors.is_empty() );
+    }
+
+    #[test]
+    fn should_recover_at_statements() {
+        let (output, errors) = parse(tokenize(FileId(0), "fun blah() { let x = ; f(1 2); x } fun other() { ) }").0);
+
+        assert_eq!( errors.len(), 3 );
+        assert_eq!( errors[0].message, "expected expression but found ';'" );
+        assert_eq!( errors[1].message, "expected ',' or ')' but found integer '2'" );
+        assert_eq!( errors[2].message, "expected expression but found ')'" );
+
+        let (stmts, result) = match &output[0] {
+            Ast::Fun(Fun { body: Expr::Block(_, stmts, result), .. }) => (stmts, result),
+            _ => panic!("not fun"),
+        };
+        assert_eq!( stmts.len(), 2 );
+        assert!( matches!( stmts[0], Stmt::Expr(Expr::Error(Span { start: 13, end: 21, .. })) ) );
+        assert!( matches!( stmts[1], Stmt::Expr(Expr::Error(Span { start: 23, end: 29, .. })) ) );
+        assert!( matches!( result.as_deref(), Some(Expr::Variable(_, n)) if n == "x" ) );
+        assert!( matches!( output[1], Ast::Fun(_) ) );
+    }
+
+    #[test]
+    fn should_recover_at_top_level() {
+        let (output, errors) = parse(tokenize(FileId(0), "fun blah( { } data X = Y; 1 2 fun other() { }").0);
+
+        assert_eq!( errors.len(), 2 );
+        assert_eq!( output.len(), 4 );
+        assert!( matches!( output[0], Ast::Error(Span { start: 0, end: 12, .. }) ) );
+        assert!( matches!( output[1], Ast::Data(_) ) );
+        assert!( matches!( output[2], Ast::Error(Span { start: 26, end: 28, .. }) ) );
+        assert!( matches!( output[3], Ast::Fun(_) ) );
+    }
+
+    #[test]
+    fn should_close_block_missing_r_curl_at_next_definition() {
+        let (output, errors) = parse(tokenize(FileId(0), "fun blah() { 1 \n fun other() { 2 }").0);
 
-    Err("TODO".into())
+        assert_eq!( errors.len(), 1 );
+        assert_eq!( errors[0].message, "expected ';' or '}' but found keyword 'fun'" );
+        assert_eq!( output.len(), 2 );
+        assert!( matches!( &output[1], Ast::Fun(f) if f.name == "other" ) );
+    }
 }
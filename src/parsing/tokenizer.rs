@@ -1,95 +1,400 @@
 
+use std::collections::VecDeque;
+
 use array_pattern::{Success, MatchError, seq, alt, pred, group};
 
+use crate::diagnostic::{Diagnostic, Phase};
+use crate::source_map::{Span, FileId};
 
-#[derive(Debug)]
-pub struct TMeta {
-    pub start : usize,
-    pub end : usize,
-}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Token {
-    LowerSymbol(TMeta, String),
-    UpperSymbol(TMeta, String),
-    Bool(TMeta, bool),
-    Number(TMeta, f64),
-    String(TMeta, String),
-    LParen(TMeta),
-    RParen(TMeta),
-    LCurl(TMeta),
-    RCurl(TMeta),
-    LSquare(TMeta),
-    RSquare(TMeta),
-    LAngle(TMeta),
-    RAngle(TMeta),
-    Comma(TMeta),
-    SemiColon(TMeta),
-    Colon(TMeta),
-    Dot(TMeta),
-    OrBar(TMeta),
-    SLArrow(TMeta),
-    SRArrow(TMeta),
-    DLArrow(TMeta),
-    DRArrow(TMeta),
+    LowerSymbol(Span, String),
+    UpperSymbol(Span, String),
+    Bool(Span, bool),
+    Let(Span),
+    Fun(Span),
+    Match(Span),
+    Data(Span),
+    If(Span),
+    Import(Span),
+    Integer(Span, i64),
+    Float(Span, f64),
+    String(Span, String),
+    Char(Span, char),
+    /// A string with holes, ie "hello {name}".
+    Interpolated(Span, Vec<Segment>),
+    LParen(Span),
+    RParen(Span),
+    LCurl(Span),
+    RCurl(Span),
+    LSquare(Span),
+    RSquare(Span),
+    LAngle(Span),
+    RAngle(Span),
+    Comma(Span),
+    SemiColon(Span),
+    Colon(Span),
+    Dot(Span),
+    OrBar(Span),
+    SLArrow(Span),
+    SRArrow(Span),
+    DLArrow(Span),
+    DRArrow(Span),
+    Equal(Span),
+    /// The text after '##'.  Unlike other comments, doc comments are tokens
+    /// because the parser attaches them to the definition that follows.
+    DocComment(Span, String),
+    Error(Span),
 }
 
-pub fn tokenize(input : &str) -> Result<Vec<Token>, String> {
-    match internal_tokenize(input) {
-        Ok(ts) => {
-            Ok(ts.into_iter()
-                 .map(|t| map(t))
-                 .filter(|t| matches!(t, Some(_)))
-                 .map(|t| t.unwrap())
-                 .collect())
-         },
-        Err(MatchError::ErrorEndOfFile | MatchError::FatalEndOfFile) =>
-            Err("Encountered unexpected end of file while tokenizing.".into()),
-        Err(MatchError::Error(i) | MatchError::Fatal(i)) => {
-            let error_reporter::ErrorReport { line, column, display } = error_reporter::report(input, i, i);
-            let ret = format!("Encountered tokenization error at line {line} and column {column}:\n\n{display}");
-            Err(ret)
-        },
+impl Token {
+    pub fn meta(&self) -> Span {
+        match self {
+            Token::LowerSymbol(m, _) => *m,
+            Token::UpperSymbol(m, _) => *m,
+            Token::Bool(m, _) => *m,
+            Token::Let(m) => *m,
+            Token::Fun(m) => *m,
+            Token::Match(m) => *m,
+            Token::Data(m) => *m,
+            Token::If(m) => *m,
+            Token::Import(m) => *m,
+            Token::Integer(m, _) => *m,
+            Token::Float(m, _) => *m,
+            Token::String(m, _) => *m,
+            Token::Char(m, _) => *m,
+            Token::Interpolated(m, _) => *m,
+            Token::LParen(m) => *m,
+            Token::RParen(m) => *m,
+            Token::LCurl(m) => *m,
+            Token::RCurl(m) => *m,
+            Token::LSquare(m) => *m,
+            Token::RSquare(m) => *m,
+            Token::LAngle(m) => *m,
+            Token::RAngle(m) => *m,
+            Token::Comma(m) => *m,
+            Token::SemiColon(m) => *m,
+            Token::Colon(m) => *m,
+            Token::Dot(m) => *m,
+            Token::OrBar(m) => *m,
+            Token::SLArrow(m) => *m,
+            Token::SRArrow(m) => *m,
+            Token::DLArrow(m) => *m,
+            Token::DRArrow(m) => *m,
+            Token::Equal(m) => *m,
+            Token::DocComment(m, _) => *m,
+            Token::Error(m) => *m,
+        }
+    }
+
+    pub fn keyword(&self) -> Option<&'static str> {
+        match self {
+            Token::Let(_) => Some("let"),
+            Token::Fun(_) => Some("fun"),
+            Token::Match(_) => Some("match"),
+            Token::Data(_) => Some("data"),
+            Token::If(_) => Some("if"),
+            Token::Import(_) => Some("import"),
+            _ => None,
+        }
+    }
+}
+
+/// Makes the token for a keyword from its span.
+type KeywordCtor = fn(Span) -> Token;
+
+/// Words which are tokens of their own instead of symbols.  Writing 'r#' in
+/// front of one (ie r#match) makes it an ordinary symbol, for names that have
+/// to match something outside of the language.
+const KEYWORDS : &[(&str, KeywordCtor)] = &[
+    ("let", Token::Let),
+    ("fun", Token::Fun),
+    ("match", Token::Match),
+    ("data", Token::Data),
+    ("if", Token::If),
+    ("import", Token::Import),
+];
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::LowerSymbol(_, s) => write!(f, "symbol '{}'", s),
+            Token::UpperSymbol(_, s) => write!(f, "symbol '{}'", s),
+            Token::Bool(_, b) => write!(f, "boolean '{}'", b),
+            Token::Let(_) | Token::Fun(_) | Token::Match(_) | Token::Data(_) | Token::If(_) | Token::Import(_) =>
+                write!(f, "keyword '{}'", self.keyword().unwrap()),
+            Token::Integer(_, n) => write!(f, "integer '{}'", n),
+            Token::Float(_, n) => write!(f, "float '{:?}'", n),
+            Token::String(_, s) => write!(f, "string {:?}", s),
+            Token::Char(_, c) => write!(f, "char {:?}", c),
+            Token::Interpolated(_, _) => write!(f, "interpolated string"),
+            Token::LParen(_) => write!(f, "'('"),
+            Token::RParen(_) => write!(f, "')'"),
+            Token::LCurl(_) => write!(f, "'{{'"),
+            Token::RCurl(_) => write!(f, "'}}'"),
+            Token::LSquare(_) => write!(f, "'['"),
+            Token::RSquare(_) => write!(f, "']'"),
+            Token::LAngle(_) => write!(f, "'<'"),
+            Token::RAngle(_) => write!(f, "'>'"),
+            Token::Comma(_) => write!(f, "','"),
+            Token::SemiColon(_) => write!(f, "';'"),
+            Token::Colon(_) => write!(f, "':'"),
+            Token::Dot(_) => write!(f, "'.'"),
+            Token::OrBar(_) => write!(f, "'|'"),
+            Token::SLArrow(_) => write!(f, "'->'"),
+            Token::SRArrow(_) => write!(f, "'<-'"),
+            Token::DLArrow(_) => write!(f, "'=>'"),
+            Token::DRArrow(_) => write!(f, "'<='"),
+            Token::Equal(_) => write!(f, "'='"),
+            Token::DocComment(_, _) => write!(f, "doc comment"),
+            Token::Error(_) => write!(f, "invalid token"),
+        }
+    }
+}
+
+/// Part of an interpolated string.
+#[derive(Debug, Clone)]
+pub enum Segment {
+    Text(String),
+    /// The tokens between the braces of a hole.  The span covers the braces.
+    Hole(Span, Vec<Token>),
+}
+
+/// Whitespace and comments.  The parser never needs trivia, but tools that
+/// rewrite source (ie the formatter) do.
+#[derive(Debug, Clone)]
+pub enum Trivia {
+    Whitespace(Span, String),
+    Comment(Span, String),
+}
+
+#[cfg(test)]
+impl Trivia {
+    pub fn text(&self) -> &str {
+        match self {
+            Trivia::Whitespace(_, s) => s,
+            Trivia::Comment(_, s) => s,
+        }
+    }
+}
+
+/// Tokens along with the trivia between them.
+#[derive(Debug, Clone)]
+pub enum Lexeme {
+    Token(Token),
+    Trivia(Trivia),
+}
+
+/// A token with its original text and the trivia around it.  Trailing trivia
+/// runs up to and including the end of the token's line, everything else
+/// before the token is leading trivia.
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    pub leading : Vec<Trivia>,
+    pub token : Token,
+    pub text : String,
+    pub trailing : Vec<Trivia>,
+}
+
+/// Tokenizes as much of the input as possible.  Invalid ranges become Error
+/// tokens and each one has a corresponding diagnostic.
+pub fn tokenize(file : FileId, input : &str) -> (Vec<Token>, Vec<Diagnostic>) {
+    let (lexemes, diagnostics) = tokenize_with_trivia(file, input);
+    let tokens = lexemes.into_iter().filter_map(|l| match l {
+        Lexeme::Token(t) => Some(t),
+        _ => None,
+    }).collect();
+    (tokens, diagnostics)
+}
+
+/// Tokenizes without losing any of the input.  Concatenating the text of the
+/// tokens (with their trivia) and then the trivia at the end of the file gives
+/// back exactly the input.
+pub fn tokenize_lossless(file : FileId, input : &str) -> (Vec<SyntaxToken>, Vec<Trivia>, Vec<Diagnostic>) {
+    let (lexemes, diagnostics) = tokenize_with_trivia(file, input);
+    let mut tokens : Vec<SyntaxToken> = vec![];
+    let mut pending = vec![];
+    // Whether trivia still belongs to the trailing trivia of the last token.
+    let mut same_line = false;
+    for lexeme in lexemes {
+        match lexeme {
+            Lexeme::Token(token) => {
+                let Span { start, end, .. } = token.meta();
+                let text = slice(input, start, end).to_string();
+                tokens.push(SyntaxToken { leading: std::mem::take(&mut pending), token, text, trailing: vec![] });
+                same_line = true;
+            },
+            Lexeme::Trivia(Trivia::Whitespace(span, w)) if same_line && w.contains('\n') => {
+                let split = w.find('\n').unwrap() + 1;
+                let last = tokens.last_mut().unwrap();
+                last.trailing.push(Trivia::Whitespace(Span { end: span.start + split - 1, ..span }, w[..split].to_string()));
+                if split < w.len() {
+                    pending.push(Trivia::Whitespace(Span { start: span.start + split, ..span }, w[split..].to_string()));
+                }
+                same_line = false;
+            },
+            Lexeme::Trivia(trivia) if same_line => tokens.last_mut().unwrap().trailing.push(trivia),
+            Lexeme::Trivia(trivia) => pending.push(trivia),
+        }
+    }
+    (tokens, pending, diagnostics)
+}
+
+/// Like tokenize, but keeps whitespace and comments.
+pub fn tokenize_with_trivia(file : FileId, input : &str) -> (Vec<Lexeme>, Vec<Diagnostic>) {
+    let internal = internal_tokenize(input);
+
+    let mut diagnostics = vec![];
+    collect_diagnostics(file, input, &internal, &mut diagnostics);
+
+    let lexemes = internal.into_iter().map(|t| map(file, input, t)).collect();
+
+    (lexemes, diagnostics)
+}
+
+/// Tokenizes lazily, one token at a time, for input too large to hold all of
+/// the tokens at once.  Trivia is skipped and invalid ranges are errors rather
+/// than Error tokens.  Errors inside the holes of a string come before the
+/// string itself.
+pub struct Tokenizer<'a> {
+    file : FileId,
+    input : &'a str,
+    chars : std::str::CharIndices<'a>,
+    pending : VecDeque<Result<Token, Diagnostic>>,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(file : FileId, input : &'a str) -> Tokenizer<'a> {
+        Tokenizer { file, input, chars: input.char_indices(), pending: VecDeque::new() }
+    }
+
+    /// The next item, without consuming it.
+    pub fn peek(&mut self) -> Option<&Result<Token, Diagnostic>> {
+        self.fill();
+        self.pending.front()
+    }
+
+    fn fill(&mut self) {
+        // Depth is only used inside of holes, which are tokenized along with their
+        // string, so there's nothing to keep between tokens here.
+        let mut depth = 0;
+        while self.pending.is_empty() {
+            let internal = match next_internal_token(&mut self.chars, false, &mut depth) {
+                Some(t) => t,
+                None => return,
+            };
+            let mut diagnostics = vec![];
+            collect_diagnostics(self.file, self.input, std::slice::from_ref(&internal), &mut diagnostics);
+            let invalid = matches!(internal.item, InternalToken::Error(_) | InternalToken::Invalid { .. });
+            self.pending.extend(diagnostics.into_iter().map(Err));
+            match map(self.file, self.input, internal) {
+                Lexeme::Token(t) if !invalid => self.pending.push_back(Ok(t)),
+                _ => { },
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fill();
+        self.pending.pop_front()
     }
 }
 
-fn map(internal : Success<InternalToken>) -> Option<Token> {
-    fn m(start:usize, end:usize) -> TMeta { TMeta { start, end } }
-    match internal {
-        Success { item: InternalToken::Junk, .. } => None,
-        Success { item: InternalToken::LowerSymbol(s), start, end } => Some(Token::LowerSymbol(m(start, end), s)),
-        Success { item: InternalToken::UpperSymbol(s), start, end } => Some(Token::UpperSymbol(m(start, end), s)),
-        Success { item: InternalToken::Bool(b), start, end } => Some(Token::Bool(m(start, end), b)),
-        Success { item: InternalToken::Number(f), start, end } => Some(Token::Number(m(start, end), f)),
-        Success { item: InternalToken::String(s), start, end } => Some(Token::String(m(start, end), s)),
-        Success { item: InternalToken::LParen, start, end } => Some(Token::LParen(m(start, end))),
-        Success { item: InternalToken::RParen, start, end } => Some(Token::RParen(m(start, end))),
-        Success { item: InternalToken::LCurl, start, end } => Some(Token::LCurl(m(start, end))),
-        Success { item: InternalToken::RCurl, start, end } => Some(Token::RCurl(m(start, end))),
-        Success { item: InternalToken::LSquare, start, end } => Some(Token::LSquare(m(start, end))),
-        Success { item: InternalToken::RSquare, start, end } => Some(Token::RSquare(m(start, end))),
-        Success { item: InternalToken::LAngle, start, end } => Some(Token::LAngle(m(start, end))),
-        Success { item: InternalToken::RAngle, start, end } => Some(Token::RAngle(m(start, end))),
-        Success { item: InternalToken::Comma, start, end } => Some(Token::Comma(m(start, end))),
-        Success { item: InternalToken::SemiColon, start, end } => Some(Token::SemiColon(m(start, end))),
-        Success { item: InternalToken::Colon, start, end } => Some(Token::Colon(m(start, end))),
-        Success { item: InternalToken::Dot, start, end } => Some(Token::Dot(m(start, end))),
-        Success { item: InternalToken::OrBar, start, end } => Some(Token::OrBar(m(start, end))),
-        Success { item: InternalToken::SLArrow, start, end } => Some(Token::SLArrow(m(start, end))),
-        Success { item: InternalToken::SRArrow, start, end } => Some(Token::SRArrow(m(start, end))),
-        Success { item: InternalToken::DLArrow, start, end } => Some(Token::DLArrow(m(start, end))),
-        Success { item: InternalToken::DRArrow, start, end } => Some(Token::DRArrow(m(start, end))),
+fn collect_diagnostics(file : FileId, input : &str, internal : &[Success<InternalToken>], ret : &mut Vec<Diagnostic>) {
+    for t in internal {
+        match t {
+            Success { item: InternalToken::Error(MatchError::Error(i) | MatchError::Fatal(i)), .. } => {
+                let c = input[*i..].chars().next().unwrap_or(' ');
+                ret.push(Diagnostic::error(Phase::Tokenize, "E0002", format!("unexpected character {:?}", c)).at(Span { file, start: *i, end: *i }));
+            },
+            Success { item: InternalToken::Error(_), start, .. } =>
+                ret.push(Diagnostic::error(Phase::Tokenize, "E0001", "unexpected end of file").at(Span { file, start: *start, end: *start })),
+            Success { item: InternalToken::Invalid { code, message, start, end }, .. } =>
+                ret.push(Diagnostic::error(Phase::Tokenize, code, message.clone()).at(Span { file, start: *start, end: *end })),
+            Success { item: InternalToken::Interpolated(pieces), .. } => {
+                for piece in pieces {
+                    if let Piece::Hole(_, _, tokens) = piece {
+                        collect_diagnostics(file, input, tokens, ret);
+                    }
+                }
+            },
+            _ => { },
+        }
     }
 }
 
+/// The text from start to end.  Ends are inclusive, so the slice has to include
+/// the whole last char.
+fn slice(input : &str, start : usize, end : usize) -> &str {
+    &input[start..end + input[end..].chars().next().map_or(0, char::len_utf8)]
+}
+
+fn map(file : FileId, input : &str, internal : Success<InternalToken>) -> Lexeme {
+    let m = |start, end| Span { file, start, end };
+    let text = |start, end| slice(input, start, end).to_string();
+    let token = match internal {
+        Success { item: InternalToken::Whitespace, start, end } => return Lexeme::Trivia(Trivia::Whitespace(m(start, end), text(start, end))),
+        Success { item: InternalToken::Comment, start, end } => return Lexeme::Trivia(Trivia::Comment(m(start, end), text(start, end))),
+        Success { item: InternalToken::LowerSymbol(s), start, end } => Token::LowerSymbol(m(start, end), s),
+        Success { item: InternalToken::UpperSymbol(s), start, end } => Token::UpperSymbol(m(start, end), s),
+        Success { item: InternalToken::Bool(b), start, end } => Token::Bool(m(start, end), b),
+        Success { item: InternalToken::Keyword(k), start, end } => k(m(start, end)),
+        Success { item: InternalToken::Integer(i), start, end } => Token::Integer(m(start, end), i),
+        Success { item: InternalToken::Float(f), start, end } => Token::Float(m(start, end), f),
+        Success { item: InternalToken::String(s), start, end } => Token::String(m(start, end), s),
+        Success { item: InternalToken::Char(c), start, end } => Token::Char(m(start, end), c),
+        Success { item: InternalToken::Interpolated(pieces), start, end } => {
+            let segments = pieces.into_iter().map(|p| match p {
+                Piece::Text(s) => Segment::Text(s),
+                Piece::Hole(open, close, tokens) => Segment::Hole(m(open, close), tokens.into_iter().filter_map(|t| match map(file, input, t) {
+                    Lexeme::Token(t) => Some(t),
+                    Lexeme::Trivia(_) => None,
+                }).collect()),
+            }).collect();
+            Token::Interpolated(m(start, end), segments)
+        },
+        Success { item: InternalToken::LParen, start, end } => Token::LParen(m(start, end)),
+        Success { item: InternalToken::RParen, start, end } => Token::RParen(m(start, end)),
+        Success { item: InternalToken::LCurl, start, end } => Token::LCurl(m(start, end)),
+        Success { item: InternalToken::RCurl, start, end } => Token::RCurl(m(start, end)),
+        Success { item: InternalToken::LSquare, start, end } => Token::LSquare(m(start, end)),
+        Success { item: InternalToken::RSquare, start, end } => Token::RSquare(m(start, end)),
+        Success { item: InternalToken::LAngle, start, end } => Token::LAngle(m(start, end)),
+        Success { item: InternalToken::RAngle, start, end } => Token::RAngle(m(start, end)),
+        Success { item: InternalToken::Comma, start, end } => Token::Comma(m(start, end)),
+        Success { item: InternalToken::SemiColon, start, end } => Token::SemiColon(m(start, end)),
+        Success { item: InternalToken::Colon, start, end } => Token::Colon(m(start, end)),
+        Success { item: InternalToken::Dot, start, end } => Token::Dot(m(start, end)),
+        Success { item: InternalToken::OrBar, start, end } => Token::OrBar(m(start, end)),
+        Success { item: InternalToken::SLArrow, start, end } => Token::SLArrow(m(start, end)),
+        Success { item: InternalToken::SRArrow, start, end } => Token::SRArrow(m(start, end)),
+        Success { item: InternalToken::DLArrow, start, end } => Token::DLArrow(m(start, end)),
+        Success { item: InternalToken::DRArrow, start, end } => Token::DRArrow(m(start, end)),
+        Success { item: InternalToken::Equal, start, end } => Token::Equal(m(start, end)),
+        Success { item: InternalToken::DocComment(s), start, end } => Token::DocComment(m(start, end), s),
+        Success { item: InternalToken::Error(_) | InternalToken::Invalid { .. }, start, end } => Token::Error(m(start, end)),
+    };
+    Lexeme::Token(token)
+}
+
 #[derive(Debug)]
 enum InternalToken {
-    Junk,
+    Whitespace,
+    Comment,
     LowerSymbol(String),
     UpperSymbol(String),
     Bool(bool),
-    Number(f64),
+    Keyword(KeywordCtor),
+    Integer(i64),
+    Float(f64),
     String(String),
+    Char(char),
+    Interpolated(Vec<Piece>),
     LParen,
     RParen,
     LCurl,
@@ -107,36 +412,402 @@ enum InternalToken {
     SRArrow,
     DLArrow,
     DRArrow,
+    Equal,
+    DocComment(String),
+    Error(MatchError),
+    /// Matched, but can't be a token (ie an integer that doesn't fit).  The
+    /// span is what the diagnostic points at.
+    Invalid { code : &'static str, message : String, start : usize, end : usize },
 }
 
+#[derive(Debug)]
+enum Piece {
+    Text(String),
+    /// The offsets of the braces and the tokens between them.
+    Hole(usize, usize, Vec<Success<InternalToken>>),
+}
+
+// Strings can have holes, ie "hello {name}", that are tokenized along with the
+// rest of the string.  A hole ends at the '}' that closes it, so it can contain
+// blocks and other strings.
 group!(string<'a>: char => InternalToken = |input| {
-    seq!(n<'a>: char => char = _n <= 'n', { '\n' });
-    seq!(r<'a>: char => char = _r <= 'r', { '\r' });
-    seq!(t<'a>: char => char = _t <= 't', { '\t' });
-    seq!(slash<'a>: char => char = _s <= '\\', { '\\' });
-    seq!(zero<'a>: char => char =  _z <= '0', { '\0' });
-    seq!(quote<'a>: char => char = _q <= '"', { '"' });
+    let mut rest = input.clone();
+    let start = match rest.next() {
+        Some((i, '"')) => i,
+        Some((i, _)) => return Err(MatchError::Error(i)),
+        None => return Err(MatchError::ErrorEndOfFile),
+    };
+
+    // Escapes are only found here and decoded afterwards, so that a bad one can
+    // be reported with its location.
+    let mut pieces = vec![];
+    let mut texts = vec![];
+    let mut text = String::new();
+    let mut text_start = start + 1;
+    let end = loop {
+        match rest.next() {
+            Some((_, '\\')) => {
+                text.push('\\');
+                match rest.next() {
+                    // The braces of a unicode escape aren't a hole.
+                    Some((_, 'u')) if matches!(rest.clone().next(), Some((_, '{'))) => {
+                        text.push('u');
+                        while let Some((_, c)) = rest.clone().next().filter(|(_, c)| c.is_ascii_hexdigit() || matches!(c, '{' | '}')) {
+                            rest.next();
+                            text.push(c);
+                            if c == '}' {
+                                break;
+                            }
+                        }
+                    },
+                    Some((_, c)) => text.push(c),
+                    None => return Err(MatchError::FatalEndOfFile),
+                }
+            },
+            Some((open, '{')) => {
+                texts.push((pieces.len(), std::mem::take(&mut text), text_start));
+                let tokens = internal_tokens(&mut rest, true);
+                match rest.next() {
+                    Some((close, '}')) => {
+                        pieces.push(Piece::Hole(open, close, tokens));
+                        text_start = close + 1;
+                    },
+                    _ => return Err(MatchError::FatalEndOfFile),
+                }
+            },
+            Some((i, '"')) => break i,
+            Some((_, c)) => text.push(c),
+            None => return Err(MatchError::FatalEndOfFile),
+        }
+    };
+    texts.push((pieces.len(), text, text_start));
+    *input = rest;
+
+    // The texts are decoded in order so that the first bad escape is the one
+    // reported.
+    let mut decoded = vec![];
+    for (index, text, offset) in texts {
+        match unescape(&text) {
+            Ok(s) => decoded.push((index, s)),
+            Err((message, s, e)) => {
+                let item = InternalToken::Invalid { code: "E0005", message, start: offset + s, end: offset + e };
+                return Ok(Success { item, start, end });
+            },
+        }
+    }
+    // Text goes in front of the hole that followed it.
+    for (index, s) in decoded.into_iter().rev().filter(|(_, s)| !s.is_empty()) {
+        pieces.insert(index, Piece::Text(s));
+    }
+    let item = match &mut pieces[..] {
+        [] => InternalToken::String(String::new()),
+        [Piece::Text(s)] => InternalToken::String(std::mem::take(s)),
+        _ => InternalToken::Interpolated(pieces),
+    };
+    Ok(Success { item, start, end })
+});
 
-    alt!(code<'a>: char => char = n | r | t | slash | zero | quote);
-    seq!(escape<'a>: char => char = _slash <= '\\', c <= code, { c });
+// Chars have the same escapes as strings:  'a', '\n' or '\u{1F600}'.
+group!(char_literal<'a>: char => InternalToken = |input| {
+    let mut rest = input.clone();
+    let start = match rest.next() {
+        Some((i, '\'')) => i,
+        Some((i, _)) => return Err(MatchError::Error(i)),
+        None => return Err(MatchError::ErrorEndOfFile),
+    };
+
+    let mut text = String::new();
+    let end = loop {
+        match rest.next() {
+            Some((_, '\\')) => {
+                text.push('\\');
+                match rest.next() {
+                    Some((_, c)) => text.push(c),
+                    None => return Err(MatchError::FatalEndOfFile),
+                }
+            },
+            Some((i, '\'')) => break i,
+            // Chars can't span lines, so the quote is most likely a typo.
+            Some((_, '\n')) => return Err(MatchError::Fatal(start)),
+            Some((_, c)) => text.push(c),
+            None => return Err(MatchError::FatalEndOfFile),
+        }
+    };
+    *input = rest;
+
+    let item = match unescape(&text) {
+        Ok(s) if s.chars().count() == 1 => InternalToken::Char(s.chars().next().unwrap()),
+        Ok(_) => InternalToken::Invalid { code: "E0006", message: "char literal must have exactly one character".into(), start, end },
+        // Offsets are relative to the text after the opening quote.
+        Err((message, s, e)) => InternalToken::Invalid { code: "E0005", message, start: start + 1 + s, end: start + 1 + e },
+    };
+    Ok(Success { item, start, end })
+});
 
-    pred!(any<'a>: char => char = |c| c != '"');
-    alt!(str_char<'a>: char => char = escape
-                                    | any  
-                                    );
+// Raw identifiers are symbols even when they are keywords:  r#match.
+group!(raw_identifier<'a>: char => InternalToken = |input| {
+    let mut rest = input.clone();
+    let start = match (rest.next(), rest.next()) {
+        (Some((i, 'r')), Some((_, '#'))) => i,
+        (Some((i, _)), _) => return Err(MatchError::Error(i)),
+        _ => return Err(MatchError::ErrorEndOfFile),
+    };
+    let mut name = String::new();
+    let mut end = start;
+    while let Some((i, c)) = rest.clone().next() {
+        let valid = match name.len() {
+            0 => c.is_lowercase() || c == '_',
+            _ => c.is_alphanumeric() || c == '_',
+        };
+        if !valid {
+            break;
+        }
+        rest.next();
+        name.push(c);
+        end = i;
+    }
+    if name.is_empty() {
+        // A raw string or 'r' followed by a comment.
+        return Err(MatchError::Error(start));
+    }
+    *input = rest;
+    Ok(Success { item: InternalToken::LowerSymbol(name), start, end })
+});
 
-    seq!(zero_or_more ~ str_chars<'a>: char => char = sc <= str_char, { sc });
+// Raw strings have no escapes and end at a quote followed by as many hashes
+// as came before the opening quote:  r"a\b" or r#"say "hi""#.
+group!(raw_string<'a>: char => InternalToken = |input| {
+    let mut rest = input.clone();
+    let start = match rest.next() {
+        Some((i, 'r')) => i,
+        Some((i, _)) => return Err(MatchError::Error(i)),
+        None => return Err(MatchError::ErrorEndOfFile),
+    };
+    let mut hashes = 0;
+    loop {
+        match rest.next() {
+            Some((_, '#')) => hashes += 1,
+            Some((_, '"')) => break,
+            // Not a raw string after all, so 'r' is a symbol.
+            Some((i, _)) => return Err(MatchError::Error(i)),
+            None => return Err(MatchError::ErrorEndOfFile),
+        }
+    }
 
-    seq!(main<'a>: char => InternalToken = _q1 <= '"', sc <= str_chars, _q2 <= '"', {
-        InternalToken::String(sc.into_iter().collect::<String>())
-    });
+    let mut text = String::new();
+    loop {
+        match rest.next() {
+            Some((i, '"')) => {
+                let mut lookahead = rest.clone();
+                let mut end = i;
+                let mut count = 0;
+                while let (true, Some((j, '#'))) = (count < hashes, lookahead.clone().next()) {
+                    lookahead.next();
+                    end = j;
+                    count += 1;
+                }
+                if count == hashes {
+                    *input = lookahead;
+                    return Ok(Success { item: InternalToken::String(text), start, end });
+                }
+                text.push('"');
+            },
+            Some((_, c)) => text.push(c),
+            None => return Err(MatchError::FatalEndOfFile),
+        }
+    }
+});
 
-    main(input)
+// Multi-line strings start with """ and a new line.  The indentation that the
+// lines (including the one with the closing """) have in common is removed, so
+// the literal can be indented along with the code around it.
+group!(multi_line_string<'a>: char => InternalToken = |input| {
+    let mut rest = input.clone();
+    let start = match (rest.next(), rest.next(), rest.next()) {
+        (Some((i, '"')), Some((_, '"')), Some((_, '"'))) => i,
+        (Some((i, _)), _, _) => return Err(MatchError::Error(i)),
+        _ => return Err(MatchError::ErrorEndOfFile),
+    };
+
+    let mut text = String::new();
+    let end = loop {
+        match rest.next() {
+            Some((_, '\\')) => {
+                text.push('\\');
+                match rest.next() {
+                    Some((_, c)) => text.push(c),
+                    None => return Err(MatchError::FatalEndOfFile),
+                }
+            },
+            Some((_, '"')) => {
+                let mut lookahead = rest.clone();
+                if let (Some((_, '"')), Some((end, '"'))) = (lookahead.next(), lookahead.next()) {
+                    rest = lookahead;
+                    break end;
+                }
+                text.push('"');
+            },
+            Some((_, c)) => text.push(c),
+            None => return Err(MatchError::FatalEndOfFile),
+        }
+    };
+    *input = rest;
+
+    // Offsets in the text are relative to the end of the opening quotes.
+    let invalid = |message, s : usize, e : usize| InternalToken::Invalid { code: "E0005", message, start: start + 3 + s, end: start + 3 + e };
+    let item = match (text.find('\n'), unescape(&text)) {
+        (_, Err((message, s, e))) => invalid(message, s, e),
+        (Some(first), _) if text[..first].trim().is_empty() =>
+            InternalToken::String(unescape(&dedent(&text[first + 1..])).expect("dedenting should keep escapes valid")),
+        (first, _) => {
+            let first = first.unwrap_or(text.len());
+            invalid("multi-line string must start on the line after \"\"\"".into(), 0, first.saturating_sub(1))
+        },
+    };
+    Ok(Success { item, start, end })
 });
 
+fn dedent(text : &str) -> String {
+    let mut lines = text.split('\n').map(|l| l.strip_suffix('\r').unwrap_or(l)).collect::<Vec<_>>();
+    let indent = |l : &str| l.len() - l.trim_start_matches([' ', '\t']).len();
+    let common = lines.iter().enumerate()
+        .filter(|(i, l)| !l.trim().is_empty() || *i == lines.len() - 1)
+        .map(|(_, l)| indent(l))
+        .min()
+        .unwrap_or(0);
+    // The closing quotes on their own line don't add a line.
+    if lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+    lines.iter().map(|l| &l[common.min(indent(l))..]).collect::<Vec<_>>().join("\n")
+}
+
+/// Replaces the escapes in the text of a string literal.  Errors have the
+/// message and the (inclusive) byte range of the bad escape in the text.
+fn unescape(text : &str) -> Result<String, (String, usize, usize)> {
+    let mut ret = String::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        let (mut end, e) = chars.next().expect("string grammar should only allow complete escapes");
+        let c = match e {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            '{' => '{',
+            '}' => '}',
+            'x' => {
+                let mut digits = String::new();
+                while let Some((i, d)) = chars.next_if(|(_, d)| d.is_ascii_hexdigit() && digits.len() < 2) {
+                    digits.push(d);
+                    end = i;
+                }
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(b) if digits.len() == 2 && b <= 0x7F => b as char,
+                    Ok(_) if digits.len() == 2 => return Err(("byte escape must be at most \\x7F".into(), start, end)),
+                    _ => return Err(("byte escape must have two hex digits".into(), start, end)),
+                }
+            },
+            'u' => {
+                let mut digits = String::new();
+                let mut closed = false;
+                if let Some((i, _)) = chars.next_if(|(_, b)| *b == '{') {
+                    end = i;
+                    while let Some((i, d)) = chars.next_if(|(_, d)| d.is_ascii_hexdigit() || *d == '}') {
+                        end = i;
+                        if d == '}' {
+                            closed = true;
+                            break;
+                        }
+                        digits.push(d);
+                    }
+                }
+                if !closed || digits.is_empty() || digits.len() > 6 {
+                    return Err(("unicode escape must look like \\u{1F600}".into(), start, end));
+                }
+                match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => return Err((format!("\\u{{{}}} is not a unicode character", digits), start, end)),
+                }
+            },
+            e => return Err((format!("unknown escape \\{}", e), start, end)),
+        };
+        ret.push(c);
+    }
+    Ok(ret)
+}
+
+/// Integers with a 0x, 0o or 0b prefix.  Everything alphanumeric after the
+/// prefix is part of the literal so that a bad digit is reported (at the digit)
+/// instead of starting a new token.  Returns None when there is no prefix.
+fn prefixed_integer(input : &mut (impl Iterator<Item = (usize, char)> + Clone)) -> Option<Success<InternalToken>> {
+    let mut rest = input.clone();
+    let (start, first) = rest.next()?;
+    let mut literal = String::from(first);
+    if first == '+' || first == '-' {
+        literal.push(rest.next()?.1);
+    }
+    let (mut end, prefix) = rest.next()?;
+    let (radix, name) = match (literal.ends_with('0'), prefix) {
+        (true, 'x') => (16, "hexadecimal"),
+        (true, 'o') => (8, "octal"),
+        (true, 'b') => (2, "binary"),
+        _ => return None,
+    };
+    literal.push(prefix);
+
+    let mut digits = String::new();
+    let mut bad_digit = None;
+    let mut lookahead = rest.clone();
+    while let Some((i, c)) = lookahead.next() {
+        if !(c.is_alphanumeric() || c == '_') {
+            break;
+        }
+        if c.is_digit(radix) {
+            digits.push(c);
+        }
+        else if c != '_' && bad_digit.is_none() {
+            bad_digit = Some((i, c));
+        }
+        literal.push(c);
+        end = i;
+        rest = lookahead.clone();
+    }
+    *input = rest;
+
+    let invalid = |code, message, start, end| InternalToken::Invalid { code, message, start, end };
+    let item = match bad_digit {
+        Some((i, c)) => invalid("E0004", format!("invalid digit {:?} in {} literal", c, name), i, i),
+        None if digits.is_empty() => invalid("E0004", format!("expected digits in {} literal", name), start, end),
+        None => {
+            let value = i128::from_str_radix(&digits, radix).ok()
+                .map(|v| if first == '-' { -v } else { v })
+                .and_then(|v| i64::try_from(v).ok());
+            match value {
+                Some(v) => InternalToken::Integer(v),
+                None => invalid("E0003", format!("integer {} is too large", literal), start, end),
+            }
+        },
+    };
+    Some(Success { item, start, end })
+}
+
 group!(number<'a>: char => InternalToken = |input| { 
-    pred!(digit<'a>: char => char = |c : char| c.is_digit(10));
-    seq!(zero_or_more ~ digits<'a>: char => char = d <= digit, { d });
+    if let Some(ret) = prefixed_integer(input) {
+        return Ok(ret);
+    }
+
+    pred!(digit<'a>: char => char = |c : char| c.is_ascii_digit());
+    // Separators are allowed anywhere after the first digit.
+    pred!(digit_or_separator<'a>: char => char = |c : char| c.is_ascii_digit() || c == '_');
+    seq!(zero_or_more ~ digits<'a>: char => char = d <= digit_or_separator, { d });
     seq!(maybe ~ dot<'a>: char => char = d <= '.', { d });
 
     seq!(little_e<'a>: char => char = e <= 'e', { e });
@@ -169,10 +840,31 @@ group!(number<'a>: char => InternalToken = |input| {
         }
     });
 
+    // A sign which isn't followed by a digit belongs to something else (ie '->').
+    let mut lookahead = input.clone();
+    match (lookahead.next(), lookahead.next()) {
+        (Some((i, '+' | '-')), Some((_, c))) if !c.is_ascii_digit() => return Err(MatchError::Error(i)),
+        (Some((i, '+' | '-')), None) => return Err(MatchError::Error(i)),
+        _ => { },
+    }
+
     match main(input) {
         Ok(Success { item, start, end }) => {
-            let ret = item.parse::<f64>().expect("allowed number string that rust fails to parse with parse::<f64>()");
-            Ok(Success { item: InternalToken::Number(ret), start, end })
+            let item = item.replace('_', "");
+            let invalid = |message| InternalToken::Invalid { code: "E0003", message, start, end };
+            let ret = if item.contains(['.', 'e', 'E']) {
+                match item.parse::<f64>() {
+                    Ok(f) => InternalToken::Float(f),
+                    Err(_) => invalid(format!("invalid number {}", item)),
+                }
+            }
+            else {
+                match item.parse::<i64>() {
+                    Ok(i) => InternalToken::Integer(i),
+                    Err(_) => invalid(format!("integer {} is too large", item)),
+                }
+            };
+            Ok(Success { item: ret, start, end })
         },
         Err(e) => Err(e),
     }
@@ -189,7 +881,10 @@ group!(lower_symbol<'a>: char => InternalToken = |input| {
         match format!( "{}{}", init, rs.into_iter().collect::<String>()) {
             x if x == "true" => InternalToken::Bool(true),
             x if x == "false" => InternalToken::Bool(false),
-            x => InternalToken::LowerSymbol(x),
+            x => match KEYWORDS.iter().find(|(k, _)| *k == x) {
+                Some((_, k)) => InternalToken::Keyword(*k),
+                None => InternalToken::LowerSymbol(x),
+            },
         }
     } );
 
@@ -211,18 +906,72 @@ group!(upper_symbol<'a>: char => InternalToken = |input| {
 group!(junk<'a>: char => InternalToken = |input| {
     pred!(p_ws<'a>: char => char = |c : char| c.is_whitespace());
     seq!(zero_or_more ~ ws<'a>: char => char = _1 <= p_ws, { '\0' });
-    seq!(whitespace<'a>: char => InternalToken = _1 <= p_ws, _2 <= ws, { InternalToken::Junk });
+    seq!(whitespace<'a>: char => InternalToken = _1 <= p_ws, _2 <= ws, { InternalToken::Whitespace });
 
-    pred!(end_line<'a>: char => char = |c : char| c == '\n' || c == '\r');
+    // The line ending is left for whitespace so that a comment can end the file.
     pred!(a<'a>: char => char = |c : char| c != '\n' && c != '\r');
     seq!(zero_or_more ~ anything<'a>: char => char = c <= a, { c });
-    seq!(comment<'a>: char => InternalToken = _1 <= '#', _2 <= anything, _3 <= end_line, { InternalToken::Junk });
+    seq!(comment<'a>: char => InternalToken = _1 <= '#', _2 <= anything, { InternalToken::Comment });
 
     alt!(main<'a>: char => InternalToken = whitespace | comment);
 
     main(input)
 });
 
+// Block comments nest so that code which already has them can be commented
+// out:  #[ a #[ b ]# c ]#
+group!(block_comment<'a>: char => InternalToken = |input| {
+    let mut rest = input.clone();
+    let start = match (rest.next(), rest.next()) {
+        (Some((i, '#')), Some((_, '['))) => i,
+        (Some((i, _)), _) => return Err(MatchError::Error(i)),
+        _ => return Err(MatchError::ErrorEndOfFile),
+    };
+
+    let mut depth = 1;
+    let end = loop {
+        match rest.next() {
+            Some((_, '#')) if matches!(rest.clone().next(), Some((_, '['))) => {
+                rest.next();
+                depth += 1;
+            },
+            Some((_, ']')) if matches!(rest.clone().next(), Some((_, '#'))) => {
+                let (end, _) = rest.next().unwrap();
+                depth -= 1;
+                if depth == 0 {
+                    break end;
+                }
+            },
+            Some(_) => { },
+            None => return Err(MatchError::FatalEndOfFile),
+        }
+    };
+    *input = rest;
+    Ok(Success { item: InternalToken::Comment, start, end })
+});
+
+// Doc comments start with exactly two hashes, so a line of them is still an
+// ordinary comment.
+group!(doc_comment<'a>: char => InternalToken = |input| {
+    let mut rest = input.clone();
+    let start = match (rest.next(), rest.next(), rest.clone().next()) {
+        (Some((i, '#')), Some((_, '#')), Some((_, '#'))) => return Err(MatchError::Error(i)),
+        (Some((i, '#')), Some((_, '#')), _) => i,
+        (Some((i, _)), _, _) => return Err(MatchError::Error(i)),
+        _ => return Err(MatchError::ErrorEndOfFile),
+    };
+
+    let mut text = String::new();
+    let mut end = start + 1;
+    while let Some((i, c)) = rest.clone().next().filter(|(_, c)| *c != '\n' && *c != '\r') {
+        rest.next();
+        text.push(c);
+        end = i;
+    }
+    *input = rest;
+    Ok(Success { item: InternalToken::DocComment(text), start, end })
+});
+
 seq!(l_paren<'a>: char => InternalToken = _1 <= '(', { InternalToken::LParen });
 seq!(r_paren<'a>: char => InternalToken = _1 <= ')', { InternalToken::RParen });
 seq!(l_curl<'a>: char => InternalToken = _1 <= '{', { InternalToken::LCurl });
@@ -236,7 +985,21 @@ seq!(dot<'a>: char => InternalToken = _1 <= '.', { InternalToken::Dot });
 seq!(or_bar<'a>: char => InternalToken = _1 <= '|', { InternalToken::OrBar });
 seq!(r_angle<'a>: char => InternalToken = _1 <= '>', { InternalToken::RAngle });
 seq!(single_left_arrow<'a>: char => InternalToken = _1 <= '-', _2 <= '>', { InternalToken::SLArrow });
-seq!(double_left_arrow<'a>: char => InternalToken = _1 <= '=', _2 <= '>', { InternalToken::DLArrow });
+
+group!(equal_group<'a>: char => InternalToken = |input| {
+    pred!(fail<'a>: char => char = |_c : char| false);
+    seq!(maybe ~ m_fail<'a>: char => () = _1 <= fail, { () });
+    seq!(equal<'a>: char => InternalToken = _1 <= m_fail, { InternalToken::Equal });
+    seq!(double_left_arrow<'a>: char => InternalToken = _1 <= '>', { InternalToken::DLArrow });
+
+    alt!(equal_options<'a>: char => InternalToken = double_left_arrow
+                                                  | equal
+                                                  );
+
+    seq!(main<'a>: char => InternalToken = _1 <= '=', x <= equal_options, { x });
+
+    main(input)
+});
 
 group!(arrow_group<'a>: char => InternalToken = |input| {
     pred!(fail<'a>: char => char = |_c : char| false);
@@ -255,15 +1018,37 @@ group!(arrow_group<'a>: char => InternalToken = |input| {
     main(input)
 });
 
-fn internal_tokenize( input : &str ) -> Result<Vec<Success<InternalToken>>, MatchError> {
+fn internal_tokenize( input : &str ) -> Vec<Success<InternalToken>> {
+    internal_tokens(&mut input.char_indices(), false)
+}
+
+/// Tokenizes to the end of the input or, for the hole of a string, up to the
+/// '}' that closes it (which is left in the input).
+fn internal_tokens( x : &mut (impl Iterator<Item=(usize,char)> + Clone), hole : bool ) -> Vec<Success<InternalToken>> {
+    let mut ret = vec![];
+    // Braces opened inside a hole.
+    let mut depth = 0;
+    while let Some(t) = next_internal_token(x, hole, &mut depth) {
+        ret.push(t);
+    }
+    ret
+}
 
-    let mut x = input.char_indices();
+/// Matches one token, or skips past an error, and returns None at the end of
+/// the input (or of the hole).
+fn next_internal_token( x : &mut (impl Iterator<Item=(usize,char)> + Clone), hole : bool, depth : &mut i32 ) -> Option<Success<InternalToken>> {
 
-    alt!( token<'a> : char => InternalToken = junk
+    alt!( token<'a> : char => InternalToken = block_comment
+                                            | doc_comment
+                                            | junk
+                                            | raw_identifier
+                                            | raw_string
                                             | lower_symbol 
                                             | upper_symbol 
                                             | number 
+                                            | multi_line_string
                                             | string 
+                                            | char_literal
                                             | l_paren
                                             | r_paren
                                             | l_curl
@@ -277,20 +1062,50 @@ fn internal_tokenize( input : &str ) -> Result<Vec<Success<InternalToken>>, Matc
                                             | or_bar
                                             | r_angle
                                             | single_left_arrow
-                                            | double_left_arrow
+                                            | equal_group
                                             | arrow_group
                                             );
 
-    let mut ret = vec![];
-    loop {
-        match token(&mut x) {
-            Ok(t) => ret.push(t),
-            Err(MatchError::ErrorEndOfFile) => break,
-            Err(e) => return Err(e),
-        }
+    fn is_sync(c : char) -> bool {
+        c.is_whitespace() || matches!(c, '(' | ')' | '{' | '}' | '[' | ']' | ',' | ';')
     }
 
-    Ok(ret)
+    let rp = x.clone();
+    match token(x) {
+        Ok(Success { item: InternalToken::RCurl, .. }) if hole && *depth == 0 => {
+            *x = rp;
+            None
+        },
+        Ok(t) => {
+            match t.item {
+                InternalToken::LCurl => *depth += 1,
+                InternalToken::RCurl => *depth -= 1,
+                _ => { },
+            }
+            Some(t)
+        },
+        Err(MatchError::ErrorEndOfFile) => None,
+        Err(e) => {
+            // Skip past the error to the next whitespace or delimiter so that
+            // the rest of the input still gets tokenized.
+            *x = rp;
+            let limit = match e {
+                MatchError::Error(i) | MatchError::Fatal(i) => i,
+                _ => usize::MAX,
+            };
+            let (start, _) = x.next()?;
+            let mut end = start;
+            let mut rp = x.clone();
+            while let Some((i, c)) = rp.next() {
+                if i >= limit && is_sync(c) {
+                    break;
+                }
+                end = i;
+                *x = rp.clone();
+            }
+            Some(Success { item: InternalToken::Error(e), start, end })
+        },
+    }
 }
 
 #[cfg(test)]
@@ -300,7 +1115,7 @@ mod test {
     #[test]
     fn should_handle_double_right_arrow() -> Result<(), MatchError> {
         let input = " <=";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 2 );
         assert_eq!( output[1].start, 1 );
@@ -313,7 +1128,7 @@ mod test {
     #[test]
     fn should_handle_single_right_arrow() -> Result<(), MatchError> {
         let input = " <-";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 2 );
         assert_eq!( output[1].start, 1 );
@@ -323,10 +1138,49 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn should_handle_double_left_arrow() -> Result<(), MatchError> {
+        let input = " =>";
+        let output = internal_tokenize(input);
+
+        assert_eq!( output.len(), 2 );
+        assert_eq!( output[1].start, 1 );
+        assert_eq!( output[1].end, 2 );
+        assert!( matches!( output[1].item, InternalToken::DLArrow ) );
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_handle_equal() -> Result<(), MatchError> {
+        let input = " = ";
+        let output = internal_tokenize(input);
+
+        assert_eq!( output.len(), 3 );
+        assert_eq!( output[1].start, 1 );
+        assert_eq!( output[1].end, 1 );
+        assert!( matches!( output[1].item, InternalToken::Equal ) );
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_handle_single_left_arrow() -> Result<(), MatchError> {
+        let input = "a->b";
+        let output = internal_tokenize(input);
+
+        assert_eq!( output.len(), 3 );
+        assert_eq!( output[1].start, 1 );
+        assert_eq!( output[1].end, 2 );
+        assert!( matches!( output[1].item, InternalToken::SLArrow ) );
+
+        Ok(())
+    }
+
     #[test]
     fn should_handle_l_angle() -> Result<(), MatchError> {
         let input = " <";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 2 );
         assert_eq!( output[1].start, 1 );
@@ -336,12 +1190,73 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn should_recover_from_unexpected_characters() {
+        let input = "f(@x, 1e~) $";
+        let (tokens, diagnostics) = tokenize(FileId(0), input);
+
+        assert_eq!( tokens.len(), 7 );
+        assert!( matches!( tokens[2], Token::Error(Span { start: 2, end: 3, .. }) ) );
+        assert!( matches!( tokens[3], Token::Comma(_) ) );
+        assert!( matches!( tokens[4], Token::Error(Span { start: 6, end: 8, .. }) ) );
+        assert!( matches!( tokens[5], Token::RParen(_) ) );
+        assert!( matches!( tokens[6], Token::Error(Span { start: 11, end: 11, .. }) ) );
+
+        assert_eq!( diagnostics.len(), 3 );
+        assert_eq!( diagnostics[0].message, "unexpected character '@'" );
+        assert!( matches!( diagnostics[1].primary, Some(Span { start: 8, end: 8, .. }) ) );
+        assert!( matches!( diagnostics[2].primary, Some(Span { start: 11, end: 11, .. }) ) );
+    }
+
+    #[test]
+    fn should_recover_from_unterminated_string() {
+        let input = "f(\"blah)";
+        let (tokens, diagnostics) = tokenize(FileId(0), input);
+
+        assert_eq!( tokens.len(), 3 );
+        assert!( matches!( tokens[2], Token::Error(Span { start: 2, end: 7, .. }) ) );
+        assert_eq!( diagnostics.len(), 1 );
+        assert_eq!( diagnostics[0].code, "E0001" );
+    }
+
+    #[test]
+    fn should_stream_tokens() {
+        let input = "## doc\nfun f(@x) { \"a{$}\" } # comment\n";
+        let (tokens, diagnostics) = tokenize(FileId(0), input);
+        let output = Tokenizer::new(FileId(0), input).collect::<Vec<_>>();
+
+        let streamed = output.iter().filter_map(|t| t.as_ref().ok()).map(|t| t.to_string()).collect::<Vec<_>>();
+        let expected = tokens.iter().filter(|t| !matches!(t, Token::Error(_))).map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!( streamed, expected );
+
+        let errors = output.iter().filter_map(|t| t.as_ref().err()).map(|e| e.message.clone()).collect::<Vec<_>>();
+        assert_eq!( errors, diagnostics.iter().map(|e| e.message.clone()).collect::<Vec<_>>() );
+        assert_eq!( errors, ["unexpected character '@'", "unexpected character '$'"] );
+        // The error in the hole comes before the string.
+        assert!( output[4].is_err() );
+        assert!( output[7].is_err() );
+        assert!( matches!( output[8], Ok(Token::Interpolated(_, _)) ) );
+    }
+
+    #[test]
+    fn should_peek_streamed_tokens() {
+        let mut tokenizer = Tokenizer::new(FileId(0), "  f (");
+
+        assert!( matches!( tokenizer.peek(), Some(Ok(Token::LowerSymbol(Span { start: 2, end: 2, .. }, _))) ) );
+        assert!( matches!( tokenizer.peek(), Some(Ok(Token::LowerSymbol(_, _))) ) );
+        assert!( matches!( tokenizer.next(), Some(Ok(Token::LowerSymbol(_, _))) ) );
+        assert!( matches!( tokenizer.peek(), Some(Ok(Token::LParen(Span { start: 4, end: 4, .. }))) ) );
+        assert!( matches!( tokenizer.next(), Some(Ok(Token::LParen(_))) ) );
+        assert!( tokenizer.peek().is_none() );
+        assert!( tokenizer.next().is_none() );
+    }
+
     #[test]
     fn should_parse_comment() -> Result<(), MatchError> {
         let input = r#"#this is a comment
                         false
         "#;
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 4 );
         assert_eq!( output[2].start, 43 );
@@ -360,7 +1275,7 @@ mod test {
     #[test]
     fn should_parse_whitespace() -> Result<(), MatchError> {
         let input = "      \n\t\rfalse";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 2 );
         assert_eq!( output[1].start, 9 );
@@ -376,10 +1291,43 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn should_keep_trivia() {
+        let (output, errors) = tokenize_with_trivia(FileId(0), "x # é\n\t# end");
+
+        assert_eq!( errors.len(), 0 );
+        assert_eq!( output.len(), 5 );
+        assert!( matches!( output[0], Lexeme::Token(Token::LowerSymbol(_, ref n)) if n == "x" ) );
+        assert!( matches!( output[1], Lexeme::Trivia(Trivia::Whitespace(_, ref w)) if w == " " ) );
+        assert!( matches!( output[2], Lexeme::Trivia(Trivia::Comment(Span { start: 2, end: 4, .. }, ref c)) if c == "# é" ) );
+        assert!( matches!( output[3], Lexeme::Trivia(Trivia::Whitespace(_, ref w)) if w == "\n\t" ) );
+        assert!( matches!( output[4], Lexeme::Trivia(Trivia::Comment(_, ref c)) if c == "# end" ) );
+    }
+
+    #[test]
+    fn should_attach_trivia() {
+        let input = "# a\nfun f() { # b\n  x\n}\n\n# c";
+        let (tokens, end, errors) = tokenize_lossless(FileId(0), input);
+
+        assert_eq!( errors.len(), 0 );
+        assert_eq!( tokens[0].leading.iter().map(|t| t.text()).collect::<String>(), "# a\n" );
+        assert_eq!( tokens[0].text, "fun" );
+        assert_eq!( tokens[4].trailing.iter().map(|t| t.text()).collect::<String>(), " # b\n" );
+        assert_eq!( tokens[5].leading.iter().map(|t| t.text()).collect::<String>(), "  " );
+        assert_eq!( tokens[6].trailing.iter().map(|t| t.text()).collect::<String>(), "\n" );
+        assert_eq!( end.iter().map(|t| t.text()).collect::<String>(), "\n# c" );
+
+        let text = tokens.iter()
+            .flat_map(|t| t.leading.iter().map(|l| l.text()).chain([t.text.as_str()]).chain(t.trailing.iter().map(|l| l.text())))
+            .chain(end.iter().map(|t| t.text()))
+            .collect::<String>();
+        assert_eq!( text, input );
+    }
+
     #[test]
     fn should_parse_string() -> Result<(), MatchError> {
         fn t(input : &str, expected : &str) -> Result<(), MatchError> {
-            let output = internal_tokenize(input)?;
+            let output = internal_tokenize(input);
 
             assert_eq!( output.len(), 1 );
             assert_eq!( output[0].start, 0 );
@@ -402,21 +1350,188 @@ mod test {
         t(r#""string \t input""#, "string \t input")?;
         t(r#""string \\ input""#, "string \\ input")?;
         t(r#""string \" input""#, "string \" input")?;
+        t(r#""string \' input""#, "string ' input")?;
+        t(r#""string \x41\x7f input""#, "string A\x7f input")?;
+        t(r#""string \u{1F600} \u{e9}\u{0} input""#, "string 😀 é\0 input")?;
+        t(r#""é \\u\{41\}""#, "é \\u{41}")?;
 
         Ok(())
     }
 
+    #[test]
+    fn should_parse_block_and_doc_comments() {
+        let input = "a #[ b #[ c ]# d ]# e\n## doc\n### not doc\n#[]#";
+        let (output, errors) = tokenize_with_trivia(FileId(0), input);
+
+        assert_eq!( errors.len(), 0 );
+        let comments = output.iter().filter_map(|l| match l {
+            Lexeme::Trivia(Trivia::Comment(m, text)) => Some((m.start, m.end, text.as_str())),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!( comments, vec![(2, 18, "#[ b #[ c ]# d ]#"), (29, 39, "### not doc"), (41, 44, "#[]#")] );
+
+        let (output, _) = tokenize(FileId(0), input);
+        assert!( matches!( output[1], Token::LowerSymbol(_, ref n) if n == "e" ) );
+        assert!( matches!( output[2], Token::DocComment(Span { start: 22, end: 27, .. }, ref text) if text == " doc" ) );
+        assert_eq!( output.len(), 3 );
+
+        let (_, errors) = tokenize(FileId(0), "a #[ b #[ c ]# d");
+        assert_eq!( errors[0].code, "E0001" );
+        assert!( matches!( errors[0].primary, Some(Span { start: 2, end: 2, .. }) ) );
+    }
+
+    #[test]
+    fn should_parse_keywords() {
+        let (output, errors) = tokenize(FileId(0), "let fun match data if import lets r#match r#if_ r#true r#_ r# r#\"s\"#");
+
+        assert_eq!( errors.len(), 0 );
+        assert!( matches!( output[0], Token::Let(Span { start: 0, end: 2, .. }) ) );
+        assert!( matches!( output[1], Token::Fun(_) ) );
+        assert!( matches!( output[2], Token::Match(_) ) );
+        assert!( matches!( output[3], Token::Data(_) ) );
+        assert!( matches!( output[4], Token::If(_) ) );
+        assert!( matches!( output[5], Token::Import(Span { start: 22, end: 27, .. }) ) );
+        assert!( matches!( output[6], Token::LowerSymbol(_, ref n) if n == "lets" ) );
+        assert!( matches!( output[7], Token::LowerSymbol(Span { start: 34, end: 40, .. }, ref n) if n == "match" ) );
+        assert!( matches!( output[8], Token::LowerSymbol(_, ref n) if n == "if_" ) );
+        assert!( matches!( output[9], Token::LowerSymbol(_, ref n) if n == "true" ) );
+        assert!( matches!( output[10], Token::LowerSymbol(_, ref n) if n == "_" ) );
+        // Without a name it's 'r' followed by a comment.
+        assert!( matches!( output[11], Token::LowerSymbol(_, ref n) if n == "r" ) );
+        assert_eq!( output.len(), 12 );
+        assert_eq!( output[0].to_string(), "keyword 'let'" );
+    }
+
+    #[test]
+    fn should_parse_chars() {
+        let (output, errors) = tokenize(FileId(0), r"'a' 'é' '\n' '\'' '\u{1F600}' '{'");
+
+        assert_eq!( errors.len(), 0 );
+        assert!( matches!( output[0], Token::Char(Span { start: 0, end: 2, .. }, 'a') ) );
+        assert!( matches!( output[1], Token::Char(Span { start: 4, end: 7, .. }, 'é') ) );
+        assert!( matches!( output[2], Token::Char(_, '\n') ) );
+        assert!( matches!( output[3], Token::Char(_, '\'') ) );
+        assert!( matches!( output[4], Token::Char(_, '😀') ) );
+        assert!( matches!( output[5], Token::Char(_, '{') ) );
+
+        let (_, errors) = tokenize(FileId(0), "'ab' '' '\\q'");
+        assert_eq!( errors[0].code, "E0006" );
+        assert!( matches!( errors[0].primary, Some(Span { start: 0, end: 3, .. }) ) );
+        assert_eq!( errors[1].code, "E0006" );
+        assert_eq!( errors[2].code, "E0005" );
+        assert!( matches!( errors[2].primary, Some(Span { start: 9, end: 10, .. }) ) );
+    }
+
+    #[test]
+    fn should_parse_raw_strings() {
+        let input = r###"r"C:\dir" r#"say "hi" # "#  r##"a"#b"## r"multi
+line" r r#x"###;
+        let (output, errors) = tokenize(FileId(0), input);
+
+        assert_eq!( errors.len(), 0 );
+        assert!( matches!( output[0], Token::String(Span { start: 0, end: 8, .. }, ref s) if s == "C:\\dir" ) );
+        assert!( matches!( output[1], Token::String(Span { start: 10, end: 25, .. }, ref s) if s == "say \"hi\" # " ) );
+        assert!( matches!( output[2], Token::String(Span { start: 28, end: 38, .. }, ref s) if s == "a\"#b" ) );
+        assert!( matches!( output[3], Token::String(_, ref s) if s == "multi\nline" ) );
+        // Without a quote 'r' is just a symbol, or a raw identifier with a hash.
+        assert!( matches!( output[4], Token::LowerSymbol(_, ref n) if n == "r" ) );
+        assert!( matches!( output[5], Token::LowerSymbol(_, ref n) if n == "x" ) );
+        assert_eq!( output.len(), 6 );
+
+        let (_, errors) = tokenize(FileId(0), "r#\"unterminated\"");
+        assert_eq!( errors[0].code, "E0001" );
+    }
+
+    #[test]
+    fn should_parse_multi_line_strings() {
+        let input = "f(\"\"\"\n    SELECT *\n      FROM \"t\"\n\n    WHERE a = '\\u{e9}'\n    \"\"\", 1)";
+        let (output, errors) = tokenize(FileId(0), input);
+
+        assert_eq!( errors.len(), 0 );
+        assert!( matches!( output[2], Token::String(Span { start: 2, end: 64, .. }, ref s)
+            if s == "SELECT *\n  FROM \"t\"\n\nWHERE a = 'é'" ) );
+        assert!( matches!( output[4], Token::Integer(_, 1) ) );
+
+        let (output, _) = tokenize(FileId(0), "\"\"\"\n  a\n b\"\"\"");
+        assert!( matches!( output[0], Token::String(_, ref s) if s == " a\nb" ) );
+
+        let (output, _) = tokenize(FileId(0), "\"\"");
+        assert!( matches!( output[0], Token::String(_, ref s) if s.is_empty() ) );
+
+        let (_, errors) = tokenize(FileId(0), "\"\"\"abc\n\"\"\"");
+        assert_eq!( errors[0].message, "multi-line string must start on the line after \"\"\"" );
+        assert!( matches!( errors[0].primary, Some(Span { start: 3, end: 5, .. }) ) );
+
+        let (_, errors) = tokenize(FileId(0), "\"\"\"\n  \\q\n\"\"\"");
+        assert!( matches!( errors[0].primary, Some(Span { start: 6, end: 7, .. }) ) );
+    }
+
+    #[test]
+    fn should_parse_interpolated_strings() {
+        let input = r#""hi {name}, {f("}")}{ {x} }\{""#;
+        let (output, errors) = tokenize(FileId(0), input);
+
+        assert_eq!( errors.len(), 0 );
+        assert_eq!( output.len(), 1 );
+        let segments = match &output[0] {
+            Token::Interpolated(Span { start: 0, end: 29, .. }, segments) => segments,
+            t => panic!("not interpolated: {:?}", t),
+        };
+        assert_eq!( segments.len(), 6 );
+        assert!( matches!( &segments[0], Segment::Text(s) if s == "hi " ) );
+        assert!( matches!( &segments[1], Segment::Hole(Span { start: 4, end: 9, .. }, ts)
+            if matches!( &ts[..], [Token::LowerSymbol(Span { start: 5, end: 8, .. }, n)] if n == "name" ) ) );
+        assert!( matches!( &segments[2], Segment::Text(s) if s == ", " ) );
+        assert!( matches!( &segments[3], Segment::Hole(Span { start: 12, end: 19, .. }, ts)
+            if matches!( &ts[..], [_, _, Token::String(Span { start: 15, end: 17, .. }, s), _] if s == "}" ) ) );
+        assert!( matches!( &segments[4], Segment::Hole(Span { start: 20, end: 26, .. }, ts) if ts.len() == 3 ) );
+        assert!( matches!( &segments[5], Segment::Text(s) if s == "{" ) );
+
+        let (_, errors) = tokenize(FileId(0), r#""a {b @} c""#);
+        assert_eq!( errors[0].code, "E0002" );
+        assert!( matches!( errors[0].primary, Some(Span { start: 6, end: 6, .. }) ) );
+
+        let (_, errors) = tokenize(FileId(0), r#""a {b""#);
+        assert_eq!( errors[0].code, "E0001" );
+    }
+
+    #[test]
+    fn should_report_invalid_escapes() {
+        fn t(input : &str, message : &str, start : usize, end : usize) {
+            let (output, errors) = tokenize(FileId(0), input);
+
+            assert_eq!( errors.len(), 1, "{}", input );
+            assert_eq!( errors[0].code, "E0005" );
+            assert_eq!( errors[0].message, message );
+            assert!( matches!( errors[0].primary, Some(Span { start: s, end: e, .. }) if s == start && e == end ), "{:?}", errors[0].primary );
+            assert_eq!( output.len(), 1 );
+            assert!( matches!( output[0], Token::Error(Span { start: 0, end: e, .. }) if e == input.len() - 1 ) );
+        }
+
+        t(r#""a\qb""#, "unknown escape \\q", 2, 3);
+        t(r#""é\x4g""#, "byte escape must have two hex digits", 3, 5);
+        t(r#""\xFF""#, "byte escape must be at most \\x7F", 1, 4);
+        t(r#""\u{110000}""#, "\\u{110000} is not a unicode character", 1, 10);
+        t(r#""\u{12""#, "unicode escape must look like \\u{1F600}", 1, 5);
+        t(r#""\u41""#, "unicode escape must look like \\u{1F600}", 1, 2);
+        t(r#""\u{}""#, "unicode escape must look like \\u{1F600}", 1, 4);
+        // Only the first bad escape is reported.
+        t(r#""\q \w""#, "unknown escape \\q", 1, 2);
+        t(r#""\q {x} \w""#, "unknown escape \\q", 1, 2);
+    }
+
     #[test]
     fn should_parse_numbers() -> Result<(), MatchError> {
         fn t(input : &str, expected : f64) -> Result<(), MatchError> {
-            let output = internal_tokenize(input)?;
+            let output = internal_tokenize(input);
 
             assert_eq!( output.len(), 1 );
             assert_eq!( output[0].start, 0 );
             assert_eq!( output[0].end, input.len() - 1 );
 
             let value = match &output[0].item {
-                InternalToken::Number(n) => *n,
+                InternalToken::Float(n) => *n,
+                InternalToken::Integer(n) => *n as f64,
                 _ => panic!("not number"),
             };
 
@@ -437,14 +1552,69 @@ mod test {
         t("1234.5678e-90", 1234.5678e-90)?;
         t("1234.5678e-901", 1234.5678e-901)?;
         t("1234", 1234.0)?;
+        t("1_000_000", 1000000.0)?;
+        t("1_000.000_5", 1000.0005)?;
+        t("1e1_0", 1e10)?;
+        t("0xFF_FF", 65535.0)?;
+        t("0xff", 255.0)?;
+        t("0o17", 15.0)?;
+        t("0b1010", 10.0)?;
+        t("-0x10", -16.0)?;
+        t("+0b1", 1.0)?;
+        t("0x7FFF_FFFF_FFFF_FFFF", i64::MAX as f64)?;
 
         Ok(())
     }
 
+    #[test]
+    fn should_report_invalid_digits() {
+        fn t(input : &str, message : &str, start : usize, end : usize) {
+            let (output, errors) = tokenize(FileId(0), input);
+
+            assert_eq!( errors.len(), 1, "{}", input );
+            assert_eq!( errors[0].message, message );
+            assert!( matches!( errors[0].primary, Some(Span { start: s, end: e, .. }) if s == start && e == end ), "{:?}", errors[0].primary );
+            // The whole literal becomes one invalid token.
+            assert_eq!( output.len(), 1 );
+            assert!( matches!( output[0], Token::Error(Span { start: 0, end: e, .. }) if e == input.len() - 1 ) );
+        }
+
+        t("0b102", "invalid digit '2' in binary literal", 4, 4);
+        t("0o1_8", "invalid digit '8' in octal literal", 4, 4);
+        t("0xFG0", "invalid digit 'G' in hexadecimal literal", 3, 3);
+        t("0x", "expected digits in hexadecimal literal", 0, 1);
+        t("0b__", "expected digits in binary literal", 0, 3);
+        t("0x1_0000_0000_0000_0000", "integer 0x1_0000_0000_0000_0000 is too large", 0, 22);
+    }
+
+    #[test]
+    fn should_separate_integers_and_floats() {
+        let (output, errors) = tokenize(FileId(0), "1234 1234.0 9007199254740993 -9223372036854775808 1e3");
+
+        assert_eq!( errors.len(), 0 );
+        assert!( matches!( output[0], Token::Integer(_, 1234) ) );
+        assert!( matches!( output[1], Token::Float(_, f) if f == 1234.0 ) );
+        assert!( matches!( output[2], Token::Integer(_, 9007199254740993) ) );
+        assert!( matches!( output[3], Token::Integer(_, i64::MIN) ) );
+        assert!( matches!( output[4], Token::Float(_, f) if f == 1000.0 ) );
+    }
+
+    #[test]
+    fn should_report_integer_overflow() {
+        let (output, errors) = tokenize(FileId(0), "f(1, 9223372036854775808)");
+
+        assert_eq!( errors.len(), 1 );
+        assert_eq!( errors[0].code, "E0003" );
+        assert_eq!( errors[0].message, "integer 9223372036854775808 is too large" );
+        assert!( matches!( errors[0].primary, Some(Span { start: 5, end: 23, .. }) ) );
+        assert!( matches!( output[4], Token::Error(Span { start: 5, end: 23, .. }) ) );
+        assert!( matches!( output[5], Token::RParen(_) ) );
+    }
+
     #[test]
     fn should_parse_boolean_starting_lower_symbol() -> Result<(), MatchError> {
         let input = "false_";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 1 );
         assert_eq!( output[0].start, 0 );
@@ -463,7 +1633,7 @@ mod test {
     #[test]
     fn should_parse_false() -> Result<(), MatchError> {
         let input = "false";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 1 );
         assert_eq!( output[0].start, 0 );
@@ -482,7 +1652,7 @@ mod test {
     #[test]
     fn should_parse_true() -> Result<(), MatchError> {
         let input = "true";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 1 );
         assert_eq!( output[0].start, 0 );
@@ -501,7 +1671,7 @@ mod test {
     #[test]
     fn should_parse_lower_symbol() -> Result<(), MatchError> {
         let input = "lower_symbol";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 1 );
         assert_eq!( output[0].start, 0 );
@@ -520,7 +1690,7 @@ mod test {
     #[test]
     fn should_parse_single_lower_symbol() -> Result<(), MatchError> {
         let input = "l";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 1 );
         assert_eq!( output[0].start, 0 );
@@ -539,7 +1709,7 @@ mod test {
     #[test]
     fn should_parse_upper_symbol() -> Result<(), MatchError> {
         let input = "UpperSymbol";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 1 );
         assert_eq!( output[0].start, 0 );
@@ -558,7 +1728,7 @@ mod test {
     #[test]
     fn should_parse_single_upper_symbol() -> Result<(), MatchError> {
         let input = "U";
-        let output = internal_tokenize(input)?;
+        let output = internal_tokenize(input);
 
         assert_eq!( output.len(), 1 );
         assert_eq!( output[0].start, 0 );
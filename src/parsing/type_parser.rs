@@ -1,11 +1,198 @@
 
+use super::tokenizer::Token;
+use crate::source_map::Span;
+use super::parser::{Input, Parsed, expect_token, unexpected_end};
+use crate::diagnostic::{Diagnostic, Phase};
 
-#[derive(Debug)]
-pub enum Ast { 
+#[derive(Debug, Clone)]
+pub enum Type {
+    Concrete(Span, String),
+    Variable(Span, String),
+    Generic(Span, String, Vec<Type>),
+    Fun(Span, Vec<Type>, Box<Type>),
+    Tuple(Span, Vec<Type>),
+    List(Span, Box<Type>),
+}
+
+impl Type {
+    pub fn meta(&self) -> Span {
+        match self {
+            Type::Concrete(m, _) => *m,
+            Type::Variable(m, _) => *m,
+            Type::Generic(m, _, _) => *m,
+            Type::Fun(m, _, _) => *m,
+            Type::Tuple(m, _) => *m,
+            Type::List(m, _) => *m,
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn comma_list(ts : &[Type]) -> String {
+            ts.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ")
+        }
+        match self {
+            Type::Concrete(_, n) => write!(f, "{}", n),
+            Type::Variable(_, n) => write!(f, "{}", n),
+            Type::Generic(_, n, args) => write!(f, "{}<{}>", n, comma_list(args)),
+            Type::Fun(_, params, ret) if params.len() == 1 && !matches!(params[0], Type::Fun(_, _, _) | Type::Tuple(_, _)) =>
+                write!(f, "{} -> {}", params[0], ret),
+            Type::Fun(_, params, ret) => write!(f, "({}) -> {}", comma_list(params), ret),
+            Type::Tuple(_, items) => write!(f, "({})", comma_list(items)),
+            Type::List(_, item) => write!(f, "[{}]", item),
+        }
+    }
+}
+
+fn m(start : Span, end : Span) -> Span {
+    Span { file: start.file, start: start.start, end: end.end }
+}
+
+/// Parses a type expression.  Function types are right associative, so 'a -> b -> c'
+/// is a function from 'a' to a function from 'b' to 'c'.  A parenthesized list before
+/// an arrow is the function's parameter list:  '(a, b) -> c'.
+pub fn parse_type(input : &mut Input) -> Parsed<Type> {
+    let start = match input.peek() {
+        Some(t) => t.meta(),
+        None => return Err(Box::new(unexpected_end("type"))),
+    };
+
+    let (param, is_paren_list) = match input.peek() {
+        Some(Token::LParen(_)) => {
+            let (end, mut items) = parse_type_list(input, "')'", |t| matches!(t, Token::RParen(_)))?;
+            if items.len() == 1 {
+                (items.pop().unwrap(), false)
+            }
+            else {
+                (Type::Tuple(m(start, end), items), true)
+            }
+        },
+        _ => (parse_atom(input)?, false),
+    };
+
+    if let Some(Token::SLArrow(_)) = input.peek() {
+        input.next();
+        let ret = parse_type(input)?;
+        let params = match param {
+            Type::Tuple(_, items) if is_paren_list => items,
+            t => vec![t],
+        };
+        return Ok(Type::Fun(m(start, ret.meta()), params, Box::new(ret)));
+    }
+
+    Ok(param)
+}
 
+fn parse_atom(input : &mut Input) -> Parsed<Type> {
+    match input.peek() {
+        Some(Token::LSquare(_)) => {
+            let start = input.next().unwrap().meta();
+            let item = parse_type(input)?;
+            let end = expect_token!(input, "']'", Token::RSquare(m) => m);
+            return Ok(Type::List(m(start, end), Box::new(item)));
+        },
+        Some(Token::UpperSymbol(_, _)) => {
+            let (start, name) = expect_token!(input, "type", Token::UpperSymbol(m, n) => (m, n));
+            if !matches!(input.peek(), Some(Token::LAngle(_))) {
+                return Ok(Type::Concrete(start, name));
+            }
+            let (end, args) = parse_type_list(input, "'>'", |t| matches!(t, Token::RAngle(_)))?;
+            if args.is_empty() {
+                return Err(Box::new(Diagnostic::error(Phase::Parse, "E0103", format!("expected type arguments for {name}")).at(m(start, end))));
+            }
+            return Ok(Type::Generic(m(start, end), name, args));
+        },
+        _ => { },
+    }
+
+    Ok(expect_token!(input, "type",
+        Token::LowerSymbol(m, n) => Type::Variable(m, n),
+    ))
+}
+
+fn parse_type_list(input : &mut Input, closing : &str, close : fn(&Token) -> bool) -> Parsed<(Span, Vec<Type>)> {
+    input.next();
+    let mut ret = vec![];
+    loop {
+        // Checking for the end first allows a trailing comma.
+        if input.peek().is_some_and(close) {
+            return Ok((input.next().unwrap().meta(), ret));
+        }
+        ret.push(parse_type(input)?);
+        expect_token!(input, &format!("',' or {closing}"),
+            Token::Comma(_) => (),
+            t if close(&t) => return Ok((t.meta(), ret)),
+        );
+    }
 }
 
-#[derive(Debug)]
-pub enum Type { 
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsing::tokenizer::tokenize;
+    use crate::source_map::FileId;
+
+    fn t(input : &str) -> Type {
+        let mut tokens = Input::new(tokenize(FileId(0), input).0);
+        let ret = parse_type(&mut tokens).unwrap();
+        assert!( tokens.peek().is_none() );
+        ret
+    }
+
+    #[test]
+    fn should_parse_simple_types() {
+        assert!( matches!( t("Number"), Type::Concrete(_, n) if n == "Number" ) );
+        assert!( matches!( t("a"), Type::Variable(_, n) if n == "a" ) );
+        assert!( matches!( t("()"), Type::Tuple(_, items) if items.is_empty() ) );
+        assert!( matches!( t("(a)"), Type::Variable(_, n) if n == "a" ) );
+    }
+
+    #[test]
+    fn should_parse_generic_type() {
+        let output = t("Map<String, List<a>>");
 
-}
\ No newline at end of file
+        assert_eq!( output.meta().start, 0 );
+        assert_eq!( output.meta().end, 19 );
+        let args = match output {
+            Type::Generic(_, n, args) if n == "Map" => args,
+            _ => panic!("not generic"),
+        };
+        assert_eq!( args.len(), 2 );
+        assert!( matches!( args[1], Type::Generic(_, ref n, ref a) if n == "List" && a.len() == 1 ) );
+    }
+
+    #[test]
+    fn should_parse_tuple_and_list_types() {
+        assert!( matches!( t("(a, Number)"), Type::Tuple(_, items) if items.len() == 2 ) );
+        assert!( matches!( t("[(a, b)]"), Type::List(_, item) if matches!(*item, Type::Tuple(_, _)) ) );
+    }
+
+    #[test]
+    fn should_parse_right_associative_function_type() {
+        let output = t("a -> b -> c");
+
+        assert_eq!( output.to_string(), "a -> b -> c" );
+        let (params, ret) = match output {
+            Type::Fun(_, params, ret) => (params, ret),
+            _ => panic!("not fun"),
+        };
+        assert_eq!( params.len(), 1 );
+        assert!( matches!( params[0], Type::Variable(_, ref n) if n == "a" ) );
+        assert!( matches!( *ret, Type::Fun(_, ref p, _) if p.len() == 1 ) );
+    }
+
+    #[test]
+    fn should_parse_multiple_parameter_function_type() {
+        assert!( matches!( t("(a, b) -> c"), Type::Fun(_, params, _) if params.len() == 2 ) );
+        assert!( matches!( t("() -> c"), Type::Fun(_, params, _) if params.is_empty() ) );
+        assert!( matches!( t("((a, b)) -> c"), Type::Fun(_, params, _)
+            if params.len() == 1 && matches!(params[0], Type::Tuple(_, _)) ) );
+        assert!( matches!( t("(a -> b) -> c"), Type::Fun(_, params, _)
+            if params.len() == 1 && matches!(params[0], Type::Fun(_, _, _)) ) );
+
+        assert_eq!( t("(a, b) -> [c]").to_string(), "(a, b) -> [c]" );
+        assert_eq!( t("((a, b)) -> c").to_string(), "((a, b)) -> c" );
+        assert_eq!( t("(a -> b) -> c").to_string(), "(a -> b) -> c" );
+    }
+}
@@ -0,0 +1,286 @@
+
+use std::io::Write;
+
+use crate::data::Stmt;
+use crate::parsing::tokenizer::{self, Token};
+use crate::parsing::parser;
+use crate::eval::value::{Value, Env};
+use crate::eval::evaluator;
+use crate::typing::infer::Checker;
+use crate::source_map::{SourceMap, FileId};
+use crate::diagnostic::Diagnostic;
+
+const HELP : &str = "\
+Enter top level definitions, 'let' statements or expressions.
+
+commands:
+    :type <expr>     show the type of an expression
+    :tokens <text>   show the tokens of some text
+    :ast <text>      show the syntax tree of some text
+    :load <file>     load the definitions in a file
+    :reset           forget all definitions
+    :help            show this message
+    :quit            exit";
+
+pub struct Repl {
+    env : Env,
+    checker : Checker,
+    sources : SourceMap,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl { env: Env::new(), checker: Checker::new(), sources: SourceMap::new() }
+    }
+
+    pub fn run(&mut self) {
+        let stdin = std::io::stdin();
+        let mut buffer = String::new();
+        loop {
+            print!("{}", if buffer.is_empty() { "dour> " } else { "....> " });
+            std::io::stdout().flush().expect("unable to flush stdout");
+
+            let mut line = String::new();
+            match stdin.read_line(&mut line) {
+                Ok(0) => return,
+                Ok(_) => { },
+                Err(e) => {
+                    eprintln!("Unable to read input: {}", e);
+                    return;
+                },
+            }
+
+            if buffer.is_empty() && line.trim() == ":quit" {
+                return;
+            }
+
+            buffer.push_str(&line);
+            if !is_complete(&buffer) {
+                continue;
+            }
+
+            let output = self.process(&buffer);
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            buffer.clear();
+        }
+    }
+
+    /// Processes one complete input and returns the text to display.
+    pub fn process(&mut self, input : &str) -> String {
+        let trimmed = input.trim();
+        if trimmed.is_empty() {
+            return String::new();
+        }
+
+        if let Some(command) = trimmed.strip_prefix(':') {
+            let (name, arg) = match command.split_once(char::is_whitespace) {
+                Some((name, arg)) => (name, arg.trim()),
+                None => (command, ""),
+            };
+            return match name {
+                "type" => match self.tokenize("<repl>", arg).and_then(|tokens| parser::parse_statement(tokens).map_err(|e| self.render_all(&e))) {
+                    // Checking a copy keeps a 'let' from being added to the environment.
+                    Ok(stmt @ Stmt::Expr(_)) => match self.checker.clone().check_statement(&stmt) {
+                        Ok(t) => t.to_string(),
+                        Err(e) => e.diagnostic().render(&self.sources),
+                    },
+                    Ok(_) => "Expected an expression.".into(),
+                    Err(e) => e,
+                },
+                "tokens" => match self.tokenize("<repl>", arg) {
+                    Ok(tokens) => tokens.iter().map(|t| t.to_string()).collect::<Vec<_>>().join("\n"),
+                    Err(e) => e,
+                },
+                "ast" => match self.tokenize("<repl>", arg) {
+                    Ok(tokens) if starts_definition(&tokens) => match parser::parse(tokens) {
+                        (asts, errors) if errors.is_empty() => format!("{:#?}", asts),
+                        (_, errors) => self.render_all(&errors),
+                    },
+                    Ok(tokens) => match parser::parse_statement(tokens) {
+                        Ok(stmt) => format!("{:#?}", stmt),
+                        Err(errors) => self.render_all(&errors),
+                    },
+                    Err(e) => e,
+                },
+                "load" => match std::fs::read_to_string(arg) {
+                    Ok(text) => match self.tokenize(arg, &text) {
+                        Ok(tokens) => self.define(tokens),
+                        Err(e) => e,
+                    },
+                    Err(e) => format!("Unable to read {}: {}", arg, e),
+                },
+                "reset" => {
+                    self.env = Env::new();
+                    self.checker = Checker::new();
+                    String::new()
+                },
+                "help" => HELP.into(),
+                _ => format!("Unknown command :{}.  Use :help to list commands.", name),
+            };
+        }
+
+        match self.tokenize("<repl>", input) {
+            Ok(tokens) if starts_definition(&tokens) => self.define(tokens),
+            Ok(tokens) => self.statement(tokens),
+            Err(e) => e,
+        }
+    }
+
+    /// Every input is kept in the source map because definitions (and so the
+    /// spans in later errors) can refer back to it.
+    fn tokenize(&mut self, name : &str, input : &str) -> Result<Vec<Token>, String> {
+        let file = self.sources.add(name, input);
+        let (tokens, errors) = tokenizer::tokenize(file, input);
+        if errors.is_empty() {
+            Ok(tokens)
+        }
+        else {
+            Err(self.render_all(&errors))
+        }
+    }
+
+    /// Every error is shown so that they can all be fixed at once.
+    fn render_all(&self, errors : &[Diagnostic]) -> String {
+        errors.iter().map(|e| e.render(&self.sources)).collect::<Vec<_>>().join("\n\n")
+    }
+
+    fn define(&mut self, tokens : Vec<Token>) -> String {
+        let asts = match parser::parse(tokens) {
+            (asts, errors) if errors.is_empty() => asts,
+            (_, errors) => return self.render_all(&errors),
+        };
+        let mut checker = self.checker.clone();
+        match checker.check(&asts) {
+            Ok(()) => {
+                self.checker = checker;
+                evaluator::load(&self.env, &asts);
+                String::new()
+            },
+            Err(e) => e.diagnostic().render(&self.sources),
+        }
+    }
+
+    fn statement(&mut self, tokens : Vec<Token>) -> String {
+        let stmt = match parser::parse_statement(tokens) {
+            Ok(stmt) => stmt,
+            Err(errors) => return self.render_all(&errors),
+        };
+        let mut checker = self.checker.clone();
+        let t = match checker.check_statement(&stmt) {
+            Ok(t) => t,
+            Err(e) => return e.diagnostic().render(&self.sources),
+        };
+        match stmt {
+            Stmt::Let { name, value, .. } => match evaluator::eval(&self.env, &value) {
+                // Lets are globals (as they are to the checker) so that later
+                // definitions can refer to them.
+                Ok(v) => {
+                    self.checker = checker;
+                    self.env.define_global(&name, v);
+                    String::new()
+                },
+                Err(e) => e.diagnostic().render(&self.sources),
+            },
+            Stmt::Expr(expr) => match evaluator::eval(&self.env, &expr) {
+                Ok(Value::Tuple(items)) if items.is_empty() => String::new(),
+                Ok(v) => format!("{} : {}", v, t),
+                Err(e) => e.diagnostic().render(&self.sources),
+            },
+        }
+    }
+}
+
+/// Top level definitions start with 'data' or 'fun' followed by a name (as
+/// opposed to a lambda expression).
+fn starts_definition(tokens : &[Token]) -> bool {
+    let code = tokens.iter().position(|t| !matches!(t, Token::DocComment(_, _))).unwrap_or(tokens.len());
+    matches!(&tokens[code..], [Token::Data(_), ..] | [Token::Fun(_), Token::LowerSymbol(_, _), ..])
+}
+
+/// Input is incomplete when it ends inside of a string or has unclosed brackets.
+pub fn is_complete(input : &str) -> bool {
+    let mut depth : i64 = 0;
+    for t in tokenizer::Tokenizer::new(FileId::NONE, input) {
+        match t {
+            Ok(Token::LCurl(_) | Token::LParen(_) | Token::LSquare(_)) => depth += 1,
+            Ok(Token::RCurl(_) | Token::RParen(_) | Token::RSquare(_)) => depth -= 1,
+            Err(e) if e.code == "E0001" => return false,
+            _ => { },
+        }
+    }
+    depth <= 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_detect_incomplete_input() {
+        assert!( !is_complete("fun f(x) {\n") );
+        assert!( !is_complete("[1, 2,\n") );
+        assert!( !is_complete("add(1,\n") );
+        assert!( !is_complete("\"unterminated\n") );
+        assert!( is_complete("fun f(x) {\n x \n}\n") );
+        assert!( is_complete("add(1, 2)\n") );
+        assert!( is_complete("}\n") );
+    }
+
+    #[test]
+    fn should_keep_definitions_and_lets() {
+        let mut repl = Repl::new();
+
+        assert_eq!( repl.process("fun double(x) { add(x, x) }\n"), "" );
+        assert_eq!( repl.process("data Box = Box(Number)\n"), "" );
+        assert_eq!( repl.process("let x = double(2);\n"), "" );
+        assert_eq!( repl.process("Box(x)\n"), "Box(4) : Box" );
+        assert_eq!( repl.process("fun (y) { add(x, y) }(1)\n"), "5 : Number" );
+
+        let mut repl = Repl::new();
+        assert_eq!( repl.process("let x = 1;\n"), "" );
+        assert_eq!( repl.process("fun f() { x }\n"), "" );
+        assert_eq!( repl.process("f()\n"), "1 : Number" );
+        assert_eq!( repl.process("let x = 2;\n"), "" );
+        assert_eq!( repl.process("f()\n"), "2 : Number" );
+    }
+
+    #[test]
+    fn should_reject_rebinding_lets_to_other_types() {
+        let mut repl = Repl::new();
+
+        assert_eq!( repl.process("let x = 1;\n"), "" );
+        assert_eq!( repl.process("fun f() { x }\n"), "" );
+        assert!( repl.process("let x = \"s\";\n").contains("x is already defined as Number and can't be rebound as String") );
+        assert_eq!( repl.process("f()\n"), "1 : Number" );
+        assert_eq!( repl.process("add(f(), 1)\n"), "2 : Number" );
+    }
+
+    #[test]
+    fn should_show_types() {
+        let mut repl = Repl::new();
+
+        repl.process("fun pair(x, y) { (x, y) }\n");
+        assert_eq!( repl.process(":type pair\n"), "(a, b) -> (a, b)" );
+        assert_eq!( repl.process(":type pair(1, [true])\n"), "(Number, [Bool])" );
+        assert!( repl.process("add(1, true)\n").contains("expected Number but found Bool") );
+    }
+
+    #[test]
+    fn should_reset() {
+        let mut repl = Repl::new();
+
+        repl.process("let x = 1;\n");
+        assert_eq!( repl.process(":reset\n"), "" );
+        assert!( repl.process("x\n").contains("unbound variable x") );
+        assert!( repl.process("fun f() { x }\n").contains("unbound variable x") );
+    }
+
+    #[test]
+    fn should_show_tokens() {
+        let mut repl = Repl::new();
+
+        assert_eq!( repl.process(":tokens f(1)\n"), "symbol 'f'\n'('\ninteger '1'\n')'" );
+    }
+}
@@ -0,0 +1,150 @@
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(pub usize);
+
+impl FileId {
+    /// For text which was not loaded into a SourceMap (ie builtin signatures).
+    pub const NONE : FileId = FileId(usize::MAX);
+}
+
+/// Byte offsets into a file.  Like the char_indices that produce them, the
+/// end is inclusive.
+#[derive(Debug, Clone, Copy)]
+pub struct Span {
+    pub file : FileId,
+    pub start : usize,
+    pub end : usize,
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name : String,
+    pub text : String,
+    line_starts : Vec<usize>,
+}
+
+pub struct Location {
+    pub line : usize,
+    pub column : usize,
+}
+
+impl SourceFile {
+    fn new(name : String, text : String) -> SourceFile {
+        let line_starts = std::iter::once(0)
+            .chain(text.char_indices().filter(|(_, c)| *c == '\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile { name, text, line_starts }
+    }
+
+    /// One based line and column (in chars) of a byte offset.
+    pub fn location(&self, offset : usize) -> Location {
+        let offset = offset.min(self.text.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let start = self.line_starts[line];
+        let column = self.text.get(start..offset).map(|s| s.chars().count()).unwrap_or(offset - start) + 1;
+        Location { line: line + 1, column }
+    }
+
+    /// Byte offset of the start of a one based line.
+    pub fn line_start(&self, line : usize) -> Option<usize> {
+        self.line_starts.get(line.checked_sub(1)?).copied()
+    }
+
+    /// The text of a one based line without its line ending.
+    pub fn line(&self, line : usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self.line_starts.get(line).copied().unwrap_or(self.text.len());
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+/// Owns every loaded file so that spans only need to carry a FileId.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files : Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { files: vec![] }
+    }
+
+    pub fn add(&mut self, name : impl Into<String>, text : impl Into<String>) -> FileId {
+        self.files.push(SourceFile::new(name.into(), text.into()));
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn get(&self, file : FileId) -> Option<&SourceFile> {
+        self.files.get(file.0)
+    }
+
+    pub fn name(&self, file : FileId) -> &str {
+        self.get(file).map(|f| f.name.as_str()).unwrap_or("<unknown>")
+    }
+
+    pub fn text(&self, file : FileId) -> &str {
+        self.get(file).map(|f| f.text.as_str()).unwrap_or("")
+    }
+
+    pub fn location(&self, span : Span) -> Location {
+        match self.get(span.file) {
+            Some(f) => f.location(span.start),
+            None => Location { line: 0, column: 0 },
+        }
+    }
+
+    /// The line containing the start of the span with the span underlined.
+    pub fn display(&self, span : Span) -> String {
+        let f = match self.get(span.file) {
+            Some(f) => f,
+            None => return String::new(),
+        };
+        let Location { line, .. } = f.location(span.start);
+        let start = span.start.min(f.text.len()) - f.line_starts[line - 1];
+        // error_reporter underlines end - start + 1 bytes, so the end is moved to
+        // make that the number of chars up to the end of the line.
+        let width = match f.text.get(span.start..=span.end.min(f.text.len().saturating_sub(1))) {
+            Some(s) if span.end >= span.start => s.chars().take_while(|c| *c != '\n').count().max(1),
+            _ => 1,
+        };
+        error_reporter::report(f.line(line), start, start + width - 1).display
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_find_lines_and_columns() {
+        let mut map = SourceMap::new();
+        let a = map.add("a.dour", "fun a() {\n    1\n}\n");
+        let b = map.add("b.dour", "λx\r\nfoo");
+
+        let Location { line, column } = map.location(Span { file: a, start: 14, end: 14 });
+        assert_eq!( (line, column), (2, 5) );
+
+        let Location { line, column } = map.location(Span { file: a, start: 0, end: 0 });
+        assert_eq!( (line, column), (1, 1) );
+
+        let Location { line, column } = map.location(Span { file: b, start: 2, end: 2 });
+        assert_eq!( (line, column), (1, 2) );
+
+        let Location { line, column } = map.location(Span { file: b, start: 6, end: 6 });
+        assert_eq!( (line, column), (2, 2) );
+
+        assert_eq!( map.name(b), "b.dour" );
+    }
+
+    #[test]
+    fn should_display_span() {
+        let mut map = SourceMap::new();
+        let a = map.add("a.dour", "fun a() {\n    blah\n}\n");
+
+        assert_eq!( map.display(Span { file: a, start: 14, end: 17 }), "    blah\n    ^^^^" );
+        assert_eq!( map.display(Span { file: a, start: 18, end: 22 }), "    blah\n        ^" );
+    }
+}
@@ -0,0 +1,975 @@
+
+use std::collections::{HashMap, HashSet};
+
+use crate::data::{Ast, Fun, Data, Param, Expr, Stmt, StringPart, Pattern};
+use crate::parsing::tokenizer;
+use crate::source_map::{Span, FileId};
+use crate::parsing::type_parser::{self, Type};
+use crate::parsing::parser::Input;
+use crate::eval::builtins::SIGNATURES;
+use crate::diagnostic::{Diagnostic, Phase};
+
+#[derive(Debug)]
+pub enum TypeError {
+    Mismatch { expected : String, expected_meta : Span, found : String, found_meta : Span },
+    InfiniteType { meta : Span, var : String, found : String },
+    UnboundVariable(Span, String),
+    UnknownConstructor(Span, String),
+    UnknownType(Span, String),
+    UnboundTypeVariable(Span, String),
+    TypeArity { meta : Span, name : String, expected : usize, found : usize },
+    ConstructorArity { meta : Span, name : String, expected : usize, found : usize },
+    ArityMismatch { meta : Span, expected : usize, found : usize },
+    Rebinding { meta : Span, name : String, expected : String, found : String },
+}
+
+impl TypeError {
+    pub fn meta(&self) -> Span {
+        match self {
+            TypeError::Mismatch { found_meta, .. } => *found_meta,
+            TypeError::InfiniteType { meta, .. } => *meta,
+            TypeError::UnboundVariable(m, _) => *m,
+            TypeError::UnknownConstructor(m, _) => *m,
+            TypeError::UnknownType(m, _) => *m,
+            TypeError::UnboundTypeVariable(m, _) => *m,
+            TypeError::TypeArity { meta, .. } => *meta,
+            TypeError::ConstructorArity { meta, .. } => *meta,
+            TypeError::ArityMismatch { meta, .. } => *meta,
+            TypeError::Rebinding { meta, .. } => *meta,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypeError::Mismatch { .. } => "E0201",
+            TypeError::InfiniteType { .. } => "E0202",
+            TypeError::UnboundVariable(_, _) => "E0203",
+            TypeError::UnknownConstructor(_, _) => "E0204",
+            TypeError::UnknownType(_, _) => "E0205",
+            TypeError::UnboundTypeVariable(_, _) => "E0206",
+            TypeError::TypeArity { .. } => "E0207",
+            TypeError::ConstructorArity { .. } => "E0208",
+            TypeError::ArityMismatch { .. } => "E0209",
+            TypeError::Rebinding { .. } => "E0210",
+        }
+    }
+
+    /// Mismatches also label the location that the expected type came from.
+    pub fn diagnostic(&self) -> Diagnostic {
+        let ret = Diagnostic::error(Phase::Type, self.code(), self.to_string()).at(self.meta());
+        match self {
+            TypeError::Mismatch { expected_meta, .. } => ret.label(*expected_meta, "Expected type comes from here"),
+            TypeError::UnboundTypeVariable(_, n) => ret.note(format!("type variables in data declarations must be listed as type parameters, ie '<{n}>'")),
+            _ => ret,
+        }
+    }
+}
+
+impl std::fmt::Display for TypeError {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeError::Mismatch { expected, found, .. } => write!(f, "expected {} but found {}", expected, found),
+            TypeError::InfiniteType { var, found, .. } => write!(f, "infinite type {} = {}", var, found),
+            TypeError::UnboundVariable(_, n) => write!(f, "unbound variable {}", n),
+            TypeError::UnknownConstructor(_, n) => write!(f, "unknown constructor {}", n),
+            TypeError::UnknownType(_, n) => write!(f, "unknown type {}", n),
+            TypeError::UnboundTypeVariable(_, n) => write!(f, "unbound type variable {}", n),
+            TypeError::TypeArity { name, expected, found, .. } =>
+                write!(f, "type {} expects {} type arguments but was given {}", name, expected, found),
+            TypeError::ConstructorArity { name, expected, found, .. } =>
+                write!(f, "constructor {} expects {} arguments but was given {}", name, expected, found),
+            TypeError::ArityMismatch { expected, found, .. } =>
+                write!(f, "function expects {} arguments but was given {}", expected, found),
+            TypeError::Rebinding { name, expected, found, .. } =>
+                write!(f, "{} is already defined as {} and can't be rebound as {}", name, expected, found),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Var(usize),
+    /// A type variable from an annotation, which only unifies with itself (and
+    /// unbound variables) so that the definition has to work for any type.
+    Rigid(usize, String),
+    Con(String, Vec<Ty>),
+    Fun(Vec<Ty>, Box<Ty>),
+    Tuple(Vec<Ty>),
+    List(Box<Ty>),
+}
+
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars : Vec<usize>,
+    ty : Ty,
+}
+
+impl Scheme {
+    fn mono(ty : Ty) -> Scheme {
+        Scheme { vars: vec![], ty }
+    }
+}
+
+/// What an annotation does with a type variable that hasn't been seen before.
+#[derive(Clone, Copy)]
+enum NewVars {
+    /// It is an error, ie in data declarations where every type variable has to
+    /// be a type parameter.
+    Reject,
+    /// It becomes a unification variable, ie in builtin signatures.
+    Flexible,
+    /// It becomes a rigid variable, ie in the annotations of definitions.
+    Rigid,
+}
+
+enum Failure {
+    Mismatch,
+    Occurs(usize, Ty),
+}
+
+const PRIMITIVES : &[&str] = &["Number", "String", "Char", "Bool"];
+
+/// Hindley-Milner type inference.  The checker keeps its environment between
+/// calls so that definitions can be added incrementally (ie by the repl).
+#[derive(Debug, Clone)]
+pub struct Checker {
+    subst : Vec<Option<Ty>>,
+    globals : HashMap<String, Scheme>,
+    locals : Vec<(String, Scheme)>,
+    constructors : HashMap<String, Scheme>,
+    data_types : HashMap<String, usize>,
+    recorded : Vec<(Span, Ty)>,
+    rigid_count : usize,
+}
+
+impl Checker {
+    pub fn new() -> Checker {
+        let mut checker = Checker {
+            subst: vec![],
+            globals: HashMap::new(),
+            locals: vec![],
+            constructors: HashMap::new(),
+            data_types: HashMap::new(),
+            recorded: vec![],
+            rigid_count: 0,
+        };
+        for (name, signature) in SIGNATURES {
+            let mut input = Input::new(tokenizer::tokenize(FileId::NONE, signature).0);
+            let t = type_parser::parse_type(&mut input).expect("builtin signature should parse");
+            let ty = checker.annotation_type(&t, &mut HashMap::new(), NewVars::Flexible).expect("builtin signature should be valid");
+            let scheme = checker.generalize(ty);
+            checker.globals.insert(name.to_string(), scheme);
+        }
+        checker
+    }
+
+    /// Checks top level definitions and adds them to the environment.
+    pub fn check(&mut self, asts : &[Ast]) -> Result<(), TypeError> {
+        self.recorded.clear();
+
+        let datas = asts.iter().filter_map(|a| match a { Ast::Data(d) => Some(d), _ => None }).collect::<Vec<_>>();
+        for d in &datas {
+            self.data_types.insert(d.name.clone(), d.type_params.len());
+        }
+        for d in &datas {
+            self.check_data(d)?;
+        }
+
+        let funs = asts.iter().filter_map(|a| match a { Ast::Fun(f) => Some(f), _ => None }).collect::<Vec<_>>();
+        for group in dependency_groups(&funs) {
+            self.check_fun_group(&group)?;
+        }
+        Ok(())
+    }
+
+    /// Checks a statement.  'let' statements add their binding to the environment.
+    /// Definitions that were already checked may refer to an earlier binding of
+    /// the same name, so a 'let' can only rebind a name to the same type.
+    pub fn check_statement(&mut self, stmt : &Stmt) -> Result<Type, TypeError> {
+        self.recorded.clear();
+        match stmt {
+            Stmt::Let { meta, name, let_type, value } => {
+                let ty = self.infer_let(let_type, value)?;
+                let scheme = self.generalize(ty.clone());
+                if let Some(expected) = self.type_of(name).map(|t| t.to_string()) {
+                    let found = self.export(&scheme.ty, *meta).to_string();
+                    if expected != found {
+                        return Err(TypeError::Rebinding { meta: *meta, name: name.clone(), expected, found });
+                    }
+                }
+                self.globals.insert(name.clone(), scheme);
+                Ok(self.export(&ty, value.meta()))
+            },
+            Stmt::Expr(e) => {
+                let ty = self.infer(e)?;
+                Ok(self.export(&ty, e.meta()))
+            },
+        }
+    }
+
+    /// The generalized type of a top level definition.
+    pub fn type_of(&self, name : &str) -> Option<Type> {
+        let scheme = self.globals.get(name)?;
+        Some(self.export(&scheme.ty, Span { file: FileId::NONE, start: 0, end: 0 }))
+    }
+
+    /// The type of every expression visited by the most recent check.
+    pub fn expression_types(&self) -> Vec<(Span, Type)> {
+        self.recorded.iter().map(|(m, ty)| (*m, self.export(ty, *m))).collect()
+    }
+
+    fn check_data(&mut self, d : &Data) -> Result<(), TypeError> {
+        let mut vars = HashMap::new();
+        let mut args = vec![];
+        for p in &d.type_params {
+            let v = self.fresh();
+            vars.insert(p.name.clone(), v.clone());
+            args.push(v);
+        }
+        let result = Ty::Con(d.name.clone(), args);
+        for c in &d.constructors {
+            let fields = c.fields.iter()
+                .map(|f| self.annotation_type(f, &mut vars, NewVars::Reject))
+                .collect::<Result<Vec<_>, _>>()?;
+            let scheme = self.generalize(Ty::Fun(fields, Box::new(result.clone())));
+            self.constructors.insert(c.name.clone(), scheme);
+        }
+        Ok(())
+    }
+
+    fn check_fun_group(&mut self, group : &[&Fun]) -> Result<(), TypeError> {
+        let mut signatures = vec![];
+        for f in group {
+            let mut vars = HashMap::new();
+            let (params, ret) = self.signature(&f.params, &f.return_type, &mut vars)?;
+            let ty = Ty::Fun(params.clone(), Box::new(ret.clone()));
+            self.globals.insert(f.name.clone(), Scheme::mono(ty.clone()));
+            signatures.push((params, ret, ty));
+        }
+
+        for (f, (params, ret, _)) in group.iter().zip(signatures.iter()) {
+            let body = self.infer_body(&f.params, params, &f.body)?;
+            let ret_meta = f.return_type.as_ref().map_or(f.meta, |t| t.meta());
+            self.unify(ret, ret_meta, &body, f.body.meta())?;
+        }
+
+        let names = group.iter().map(|f| f.name.clone()).collect::<HashSet<_>>();
+        for name in &names {
+            self.globals.remove(name);
+        }
+        for (f, (_, _, ty)) in group.iter().zip(signatures) {
+            let ty = self.loosen(&ty);
+            let scheme = self.generalize(ty);
+            self.globals.insert(f.name.clone(), scheme);
+        }
+        Ok(())
+    }
+
+    fn signature(&mut self, params : &[Param], return_type : &Option<Type>, vars : &mut HashMap<String, Ty>) -> Result<(Vec<Ty>, Ty), TypeError> {
+        let mut ps = vec![];
+        for p in params {
+            match &p.param_type {
+                Some(t) => ps.push(self.annotation_type(t, vars, NewVars::Rigid)?),
+                None => ps.push(self.fresh()),
+            }
+        }
+        let ret = match return_type {
+            Some(t) => self.annotation_type(t, vars, NewVars::Rigid)?,
+            None => self.fresh(),
+        };
+        Ok((ps, ret))
+    }
+
+    fn infer_body(&mut self, params : &[Param], param_types : &[Ty], body : &Expr) -> Result<Ty, TypeError> {
+        let mark = self.locals.len();
+        for (p, ty) in params.iter().zip(param_types.iter()) {
+            self.locals.push((p.name.clone(), Scheme::mono(ty.clone())));
+            self.recorded.push((p.meta, ty.clone()));
+        }
+        let ret = self.infer(body);
+        self.locals.truncate(mark);
+        ret
+    }
+
+    fn infer_let(&mut self, let_type : &Option<Type>, value : &Expr) -> Result<Ty, TypeError> {
+        let ty = self.infer(value)?;
+        if let Some(t) = let_type {
+            let expected = self.annotation_type(t, &mut HashMap::new(), NewVars::Rigid)?;
+            self.unify(&expected, t.meta(), &ty, value.meta())?;
+        }
+        Ok(self.loosen(&ty))
+    }
+
+    fn infer(&mut self, expr : &Expr) -> Result<Ty, TypeError> {
+        let ty = self.infer_expr(expr)?;
+        self.recorded.push((expr.meta(), ty.clone()));
+        Ok(ty)
+    }
+
+    fn infer_expr(&mut self, expr : &Expr) -> Result<Ty, TypeError> {
+        match expr {
+            Expr::Integer(_, _) | Expr::Number(_, _) => Ok(con("Number")),
+            Expr::String(_, _) => Ok(con("String")),
+            Expr::Char(_, _) => Ok(con("Char")),
+            // Any value can be put in a string.
+            Expr::Interpolation(_, parts) => {
+                for part in parts {
+                    if let StringPart::Expr(e) = part {
+                        self.infer(e)?;
+                    }
+                }
+                Ok(con("String"))
+            },
+            Expr::Bool(_, _) => Ok(con("Bool")),
+            Expr::Variable(m, n) => {
+                let scheme = self.locals.iter().rev().find(|(name, _)| name == n).map(|(_, s)| s.clone())
+                    .or_else(|| self.globals.get(n).cloned());
+                match scheme {
+                    Some(s) => Ok(self.instantiate(&s)),
+                    None => Err(TypeError::UnboundVariable(*m, n.clone())),
+                }
+            },
+            Expr::Constructor(m, name, args) => {
+                let (fields, result) = self.constructor(*m, name, args.len())?;
+                for (field, arg) in fields.iter().zip(args.iter()) {
+                    let arg_ty = self.infer(arg)?;
+                    self.unify(field, *m, &arg_ty, arg.meta())?;
+                }
+                Ok(result)
+            },
+            Expr::Tuple(_, items) => {
+                let items = items.iter().map(|i| self.infer(i)).collect::<Result<Vec<_>, _>>()?;
+                Ok(Ty::Tuple(items))
+            },
+            Expr::List(_, items) => {
+                let item_ty = match items.first() {
+                    Some(first) => {
+                        let first_ty = self.infer(first)?;
+                        for item in &items[1..] {
+                            let ty = self.infer(item)?;
+                            self.unify(&first_ty, first.meta(), &ty, item.meta())?;
+                        }
+                        first_ty
+                    },
+                    None => self.fresh(),
+                };
+                Ok(Ty::List(Box::new(item_ty)))
+            },
+            Expr::Call(m, f, args) => {
+                let f_ty = self.infer(f)?;
+                let (params, ret) = match self.prune(&f_ty) {
+                    Ty::Fun(params, ret) => (params, *ret),
+                    _ => {
+                        let params = args.iter().map(|_| self.fresh()).collect::<Vec<_>>();
+                        let ret = self.fresh();
+                        let expected = Ty::Fun(params.clone(), Box::new(ret.clone()));
+                        self.unify(&expected, *m, &f_ty, f.meta())?;
+                        (params, ret)
+                    },
+                };
+                if params.len() != args.len() {
+                    return Err(TypeError::ArityMismatch { meta: *m, expected: params.len(), found: args.len() });
+                }
+                for (param, arg) in params.iter().zip(args.iter()) {
+                    let arg_ty = self.infer(arg)?;
+                    self.unify(param, f.meta(), &arg_ty, arg.meta())?;
+                }
+                Ok(ret)
+            },
+            Expr::Block(_, stmts, result) => {
+                let mark = self.locals.len();
+                let ret = self.infer_block(stmts, result);
+                self.locals.truncate(mark);
+                ret
+            },
+            Expr::Match(_, target, arms) => {
+                let target_ty = self.infer(target)?;
+                let mut result : Option<(Ty, Span)> = None;
+                for arm in arms {
+                    let mut bindings = vec![];
+                    let pattern_ty = self.infer_pattern(&arm.pattern, &mut bindings)?;
+                    self.unify(&target_ty, target.meta(), &pattern_ty, arm.pattern.meta())?;
+
+                    let mark = self.locals.len();
+                    self.locals.extend(bindings.into_iter().map(|(n, t)| (n, Scheme::mono(t))));
+                    let body_ty = self.infer(&arm.body);
+                    self.locals.truncate(mark);
+                    let body_ty = body_ty?;
+
+                    match &result {
+                        Some((ty, meta)) => {
+                            let (ty, meta) = (ty.clone(), *meta);
+                            self.unify(&ty, meta, &body_ty, arm.body.meta())?;
+                        },
+                        None => result = Some((body_ty, arm.body.meta())),
+                    }
+                }
+                match result {
+                    Some((ty, _)) => Ok(ty),
+                    None => Ok(self.fresh()),
+                }
+            },
+            Expr::Lambda(_, params, return_type, body) => {
+                let (param_types, ret) = self.signature(params, return_type, &mut HashMap::new())?;
+                let body_ty = self.infer_body(params, &param_types, body)?;
+                let ret_meta = return_type.as_ref().map_or(body.meta(), |t| t.meta());
+                self.unify(&ret, ret_meta, &body_ty, body.meta())?;
+                Ok(Ty::Fun(param_types, Box::new(ret)))
+            },
+            // The parse error has already been reported, so anything goes.
+            Expr::Error(_) => Ok(self.fresh()),
+        }
+    }
+
+    fn infer_block(&mut self, stmts : &[Stmt], result : &Option<Box<Expr>>) -> Result<Ty, TypeError> {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Let { name, let_type, value, .. } => {
+                    let ty = self.infer_let(let_type, value)?;
+                    let scheme = self.generalize(ty);
+                    self.locals.push((name.clone(), scheme));
+                },
+                Stmt::Expr(e) => { self.infer(e)?; },
+            }
+        }
+        match result {
+            Some(e) => self.infer(e),
+            None => Ok(Ty::Tuple(vec![])),
+        }
+    }
+
+    fn infer_pattern(&mut self, pattern : &Pattern, bindings : &mut Vec<(String, Ty)>) -> Result<Ty, TypeError> {
+        let ty = match pattern {
+            Pattern::Integer(_, _) | Pattern::Number(_, _) => con("Number"),
+            Pattern::String(_, _) => con("String"),
+            Pattern::Char(_, _) => con("Char"),
+            Pattern::Bool(_, _) => con("Bool"),
+            Pattern::Wildcard(_) => self.fresh(),
+            Pattern::Variable(_, n) => {
+                let ty = self.fresh();
+                bindings.push((n.clone(), ty.clone()));
+                ty
+            },
+            Pattern::Constructor(m, name, ps) => {
+                let (fields, result) = self.constructor(*m, name, ps.len())?;
+                for (field, p) in fields.iter().zip(ps.iter()) {
+                    let p_ty = self.infer_pattern(p, bindings)?;
+                    self.unify(field, *m, &p_ty, p.meta())?;
+                }
+                result
+            },
+            Pattern::Tuple(_, ps) => {
+                let items = ps.iter().map(|p| self.infer_pattern(p, bindings)).collect::<Result<Vec<_>, _>>()?;
+                Ty::Tuple(items)
+            },
+            Pattern::List(m, ps, rest) => {
+                let item_ty = self.fresh();
+                for p in ps {
+                    let p_ty = self.infer_pattern(p, bindings)?;
+                    self.unify(&item_ty, *m, &p_ty, p.meta())?;
+                }
+                let list_ty = Ty::List(Box::new(item_ty));
+                if let Some(rest) = rest {
+                    let rest_ty = self.infer_pattern(rest, bindings)?;
+                    self.unify(&list_ty, *m, &rest_ty, rest.meta())?;
+                }
+                list_ty
+            },
+        };
+        self.recorded.push((pattern.meta(), ty.clone()));
+        Ok(ty)
+    }
+
+    fn constructor(&mut self, meta : Span, name : &str, arg_count : usize) -> Result<(Vec<Ty>, Ty), TypeError> {
+        let scheme = match self.constructors.get(name) {
+            Some(s) => s.clone(),
+            None => return Err(TypeError::UnknownConstructor(meta, name.to_string())),
+        };
+        match self.instantiate(&scheme) {
+            Ty::Fun(fields, _) if fields.len() != arg_count =>
+                Err(TypeError::ConstructorArity { meta, name: name.to_string(), expected: fields.len(), found: arg_count }),
+            Ty::Fun(fields, result) => Ok((fields, *result)),
+            _ => unreachable!("constructor scheme should be a function"),
+        }
+    }
+
+    fn annotation_type(&mut self, t : &Type, vars : &mut HashMap<String, Ty>, new_vars : NewVars) -> Result<Ty, TypeError> {
+        match t {
+            Type::Concrete(_, n) if PRIMITIVES.contains(&n.as_str()) => Ok(con(n)),
+            Type::Concrete(m, n) => self.named_type(*m, n, vec![]),
+            Type::Generic(m, n, args) => {
+                let args = args.iter().map(|a| self.annotation_type(a, vars, new_vars)).collect::<Result<Vec<_>, _>>()?;
+                self.named_type(*m, n, args)
+            },
+            Type::Variable(m, n) => {
+                if let Some(ty) = vars.get(n) {
+                    return Ok(ty.clone());
+                }
+                let ty = match new_vars {
+                    NewVars::Reject => return Err(TypeError::UnboundTypeVariable(*m, n.clone())),
+                    NewVars::Flexible => self.fresh(),
+                    NewVars::Rigid => {
+                        self.rigid_count += 1;
+                        Ty::Rigid(self.rigid_count, n.clone())
+                    },
+                };
+                vars.insert(n.clone(), ty.clone());
+                Ok(ty)
+            },
+            Type::Fun(_, params, ret) => {
+                let params = params.iter().map(|p| self.annotation_type(p, vars, new_vars)).collect::<Result<Vec<_>, _>>()?;
+                let ret = self.annotation_type(ret, vars, new_vars)?;
+                Ok(Ty::Fun(params, Box::new(ret)))
+            },
+            Type::Tuple(_, items) => {
+                let items = items.iter().map(|i| self.annotation_type(i, vars, new_vars)).collect::<Result<Vec<_>, _>>()?;
+                Ok(Ty::Tuple(items))
+            },
+            Type::List(_, item) => Ok(Ty::List(Box::new(self.annotation_type(item, vars, new_vars)?))),
+        }
+    }
+
+    fn named_type(&mut self, meta : Span, name : &str, args : Vec<Ty>) -> Result<Ty, TypeError> {
+        match self.data_types.get(name) {
+            Some(arity) if *arity == args.len() => Ok(Ty::Con(name.to_string(), args)),
+            Some(arity) => Err(TypeError::TypeArity { meta, name: name.to_string(), expected: *arity, found: args.len() }),
+            None if PRIMITIVES.contains(&name) =>
+                Err(TypeError::TypeArity { meta, name: name.to_string(), expected: 0, found: args.len() }),
+            None => Err(TypeError::UnknownType(meta, name.to_string())),
+        }
+    }
+
+    fn fresh(&mut self) -> Ty {
+        self.subst.push(None);
+        Ty::Var(self.subst.len() - 1)
+    }
+
+    /// Replaces rigid variables with fresh ones once the annotated definition has
+    /// been checked, so that it can be generalized like any other.
+    fn loosen(&mut self, ty : &Ty) -> Ty {
+        fn replace(checker : &mut Checker, ty : &Ty, map : &mut HashMap<usize, Ty>) -> Ty {
+            match ty {
+                Ty::Rigid(r, _) => match map.get(r) {
+                    Some(v) => v.clone(),
+                    None => {
+                        let v = checker.fresh();
+                        map.insert(*r, v.clone());
+                        v
+                    },
+                },
+                Ty::Var(v) => Ty::Var(*v),
+                Ty::Con(n, args) => Ty::Con(n.clone(), args.iter().map(|a| replace(checker, a, map)).collect()),
+                Ty::Fun(params, ret) => {
+                    let params = params.iter().map(|p| replace(checker, p, map)).collect();
+                    Ty::Fun(params, Box::new(replace(checker, ret, map)))
+                },
+                Ty::Tuple(items) => Ty::Tuple(items.iter().map(|i| replace(checker, i, map)).collect()),
+                Ty::List(item) => Ty::List(Box::new(replace(checker, item, map))),
+            }
+        }
+        let ty = self.resolve(ty);
+        replace(self, &ty, &mut HashMap::new())
+    }
+
+    /// Follows variable bindings until reaching an unbound variable or a type
+    /// constructor.
+    fn prune(&self, ty : &Ty) -> Ty {
+        match ty {
+            Ty::Var(v) => match &self.subst[*v] {
+                Some(t) => self.prune(t),
+                None => ty.clone(),
+            },
+            t => t.clone(),
+        }
+    }
+
+    fn resolve(&self, ty : &Ty) -> Ty {
+        match self.prune(ty) {
+            Ty::Var(v) => Ty::Var(v),
+            Ty::Rigid(r, n) => Ty::Rigid(r, n),
+            Ty::Con(n, args) => Ty::Con(n, args.iter().map(|a| self.resolve(a)).collect()),
+            Ty::Fun(params, ret) => Ty::Fun(params.iter().map(|p| self.resolve(p)).collect(), Box::new(self.resolve(&ret))),
+            Ty::Tuple(items) => Ty::Tuple(items.iter().map(|i| self.resolve(i)).collect()),
+            Ty::List(item) => Ty::List(Box::new(self.resolve(&item))),
+        }
+    }
+
+    fn unify(&mut self, expected : &Ty, expected_meta : Span, found : &Ty, found_meta : Span) -> Result<(), TypeError> {
+        match self.unify_types(expected, found) {
+            Ok(()) => Ok(()),
+            Err(Failure::Mismatch) => {
+                let mut names = HashMap::new();
+                let expected = self.export_with(&self.resolve(expected), expected_meta, &mut names).to_string();
+                let found = self.export_with(&self.resolve(found), found_meta, &mut names).to_string();
+                Err(TypeError::Mismatch { expected, expected_meta, found, found_meta })
+            },
+            Err(Failure::Occurs(v, ty)) => {
+                let mut names = HashMap::new();
+                let var = self.export_with(&Ty::Var(v), found_meta, &mut names).to_string();
+                let found = self.export_with(&self.resolve(&ty), found_meta, &mut names).to_string();
+                Err(TypeError::InfiniteType { meta: found_meta, var, found })
+            },
+        }
+    }
+
+    fn unify_types(&mut self, a : &Ty, b : &Ty) -> Result<(), Failure> {
+        fn all(checker : &mut Checker, xs : &[Ty], ys : &[Ty]) -> Result<(), Failure> {
+            if xs.len() != ys.len() {
+                return Err(Failure::Mismatch);
+            }
+            for (x, y) in xs.iter().zip(ys.iter()) {
+                checker.unify_types(x, y)?;
+            }
+            Ok(())
+        }
+        match (self.prune(a), self.prune(b)) {
+            (Ty::Var(x), Ty::Var(y)) if x == y => Ok(()),
+            (Ty::Var(x), t) | (t, Ty::Var(x)) => {
+                if self.occurs(x, &t) {
+                    return Err(Failure::Occurs(x, t));
+                }
+                self.subst[x] = Some(t);
+                Ok(())
+            },
+            (Ty::Rigid(x, _), Ty::Rigid(y, _)) if x == y => Ok(()),
+            (Ty::Con(x, xs), Ty::Con(y, ys)) if x == y => all(self, &xs, &ys),
+            (Ty::Fun(xs, x), Ty::Fun(ys, y)) => {
+                all(self, &xs, &ys)?;
+                self.unify_types(&x, &y)
+            },
+            (Ty::Tuple(xs), Ty::Tuple(ys)) => all(self, &xs, &ys),
+            (Ty::List(x), Ty::List(y)) => self.unify_types(&x, &y),
+            _ => Err(Failure::Mismatch),
+        }
+    }
+
+    fn occurs(&self, v : usize, ty : &Ty) -> bool {
+        match self.prune(ty) {
+            Ty::Var(x) => x == v,
+            Ty::Rigid(_, _) => false,
+            Ty::Con(_, args) => args.iter().any(|a| self.occurs(v, a)),
+            Ty::Fun(params, ret) => params.iter().any(|p| self.occurs(v, p)) || self.occurs(v, &ret),
+            Ty::Tuple(items) => items.iter().any(|i| self.occurs(v, i)),
+            Ty::List(item) => self.occurs(v, &item),
+        }
+    }
+
+    fn free_vars(&self, ty : &Ty, ret : &mut Vec<usize>) {
+        match self.prune(ty) {
+            Ty::Var(x) => if !ret.contains(&x) { ret.push(x) },
+            Ty::Rigid(_, _) => { },
+            Ty::Con(_, args) => args.iter().for_each(|a| self.free_vars(a, ret)),
+            Ty::Fun(params, r) => {
+                params.iter().for_each(|p| self.free_vars(p, ret));
+                self.free_vars(&r, ret);
+            },
+            Ty::Tuple(items) => items.iter().for_each(|i| self.free_vars(i, ret)),
+            Ty::List(item) => self.free_vars(&item, ret),
+        }
+    }
+
+    fn generalize(&self, ty : Ty) -> Scheme {
+        let mut env_vars = vec![];
+        for scheme in self.locals.iter().map(|(_, s)| s).chain(self.globals.values()) {
+            let mut vs = vec![];
+            self.free_vars(&scheme.ty, &mut vs);
+            env_vars.extend(vs.into_iter().filter(|v| !scheme.vars.contains(v)));
+        }
+        let mut vars = vec![];
+        self.free_vars(&ty, &mut vars);
+        vars.retain(|v| !env_vars.contains(v));
+        Scheme { vars, ty: self.resolve(&ty) }
+    }
+
+    fn instantiate(&mut self, scheme : &Scheme) -> Ty {
+        fn replace(ty : &Ty, map : &HashMap<usize, Ty>) -> Ty {
+            match ty {
+                Ty::Var(v) => map.get(v).cloned().unwrap_or(Ty::Var(*v)),
+                Ty::Rigid(r, n) => Ty::Rigid(*r, n.clone()),
+                Ty::Con(n, args) => Ty::Con(n.clone(), args.iter().map(|a| replace(a, map)).collect()),
+                Ty::Fun(params, ret) => Ty::Fun(params.iter().map(|p| replace(p, map)).collect(), Box::new(replace(ret, map))),
+                Ty::Tuple(items) => Ty::Tuple(items.iter().map(|i| replace(i, map)).collect()),
+                Ty::List(item) => Ty::List(Box::new(replace(item, map))),
+            }
+        }
+        let map = scheme.vars.iter().map(|v| (*v, self.fresh())).collect::<HashMap<_, _>>();
+        replace(&self.resolve(&scheme.ty), &map)
+    }
+
+    fn export(&self, ty : &Ty, meta : Span) -> Type {
+        self.export_with(&self.resolve(ty), meta, &mut HashMap::new())
+    }
+
+    fn export_with(&self, ty : &Ty, meta : Span, names : &mut HashMap<usize, String>) -> Type {
+        match ty {
+            Ty::Var(v) => {
+                let count = names.len();
+                let name = names.entry(*v).or_insert_with(|| var_name(count));
+                Type::Variable(meta, name.clone())
+            },
+            Ty::Rigid(_, n) => Type::Variable(meta, n.clone()),
+            Ty::Con(n, args) if args.is_empty() => Type::Concrete(meta, n.clone()),
+            Ty::Con(n, args) => Type::Generic(meta, n.clone(), args.iter().map(|a| self.export_with(a, meta, names)).collect()),
+            Ty::Fun(params, ret) => {
+                let params = params.iter().map(|p| self.export_with(p, meta, names)).collect();
+                Type::Fun(meta, params, Box::new(self.export_with(ret, meta, names)))
+            },
+            Ty::Tuple(items) => Type::Tuple(meta, items.iter().map(|i| self.export_with(i, meta, names)).collect()),
+            Ty::List(item) => Type::List(meta, Box::new(self.export_with(item, meta, names))),
+        }
+    }
+}
+
+fn con(name : &str) -> Ty {
+    Ty::Con(name.to_string(), vec![])
+}
+
+fn var_name(index : usize) -> String {
+    let letter = (b'a' + (index % 26) as u8) as char;
+    match index / 26 {
+        0 => letter.to_string(),
+        n => format!("{}{}", letter, n),
+    }
+}
+
+/// Groups functions into strongly connected components of the call graph in
+/// dependency order so that each group can be generalized before it is used.
+fn dependency_groups<'a>(funs : &[&'a Fun]) -> Vec<Vec<&'a Fun>> {
+    fn references(expr : &Expr, ret : &mut HashSet<String>) {
+        match expr {
+            Expr::Variable(_, n) => { ret.insert(n.clone()); },
+            Expr::Integer(_, _) | Expr::Number(_, _) | Expr::String(_, _) | Expr::Char(_, _) | Expr::Bool(_, _) => { },
+            Expr::Interpolation(_, parts) => parts.iter().for_each(|p| if let StringPart::Expr(e) = p { references(e, ret) }),
+            Expr::Constructor(_, _, es) | Expr::Tuple(_, es) | Expr::List(_, es) => es.iter().for_each(|e| references(e, ret)),
+            Expr::Call(_, f, es) => {
+                references(f, ret);
+                es.iter().for_each(|e| references(e, ret));
+            },
+            Expr::Block(_, stmts, result) => {
+                for stmt in stmts {
+                    match stmt {
+                        Stmt::Let { value, .. } => references(value, ret),
+                        Stmt::Expr(e) => references(e, ret),
+                    }
+                }
+                if let Some(e) = result {
+                    references(e, ret);
+                }
+            },
+            Expr::Match(_, target, arms) => {
+                references(target, ret);
+                arms.iter().for_each(|a| references(&a.body, ret));
+            },
+            Expr::Lambda(_, _, _, body) => references(body, ret),
+            Expr::Error(_) => { },
+        }
+    }
+
+    struct Tarjan<'a, 'b> {
+        funs : &'b [&'a Fun],
+        edges : Vec<Vec<usize>>,
+        index : Vec<Option<usize>>,
+        low : Vec<usize>,
+        on_stack : Vec<bool>,
+        stack : Vec<usize>,
+        counter : usize,
+        groups : Vec<Vec<&'a Fun>>,
+    }
+
+    impl<'a, 'b> Tarjan<'a, 'b> {
+        fn visit(&mut self, v : usize) {
+            self.index[v] = Some(self.counter);
+            self.low[v] = self.counter;
+            self.counter += 1;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+
+            for w in self.edges[v].clone() {
+                match self.index[w] {
+                    None => {
+                        self.visit(w);
+                        self.low[v] = self.low[v].min(self.low[w]);
+                    },
+                    Some(i) if self.on_stack[w] => self.low[v] = self.low[v].min(i),
+                    Some(_) => { },
+                }
+            }
+
+            if Some(self.low[v]) == self.index[v] {
+                let mut group = vec![];
+                loop {
+                    let w = self.stack.pop().unwrap();
+                    self.on_stack[w] = false;
+                    group.push(self.funs[w]);
+                    if w == v {
+                        break;
+                    }
+                }
+                group.reverse();
+                self.groups.push(group);
+            }
+        }
+    }
+
+    let names = funs.iter().enumerate().map(|(i, f)| (f.name.clone(), i)).collect::<HashMap<_, _>>();
+    let edges = funs.iter().map(|f| {
+        let mut refs = HashSet::new();
+        references(&f.body, &mut refs);
+        let mut es = refs.iter().filter_map(|r| names.get(r).cloned()).collect::<Vec<_>>();
+        es.sort();
+        es
+    }).collect::<Vec<_>>();
+
+    let mut tarjan = Tarjan {
+        funs,
+        edges,
+        index: vec![None; funs.len()],
+        low: vec![0; funs.len()],
+        on_stack: vec![false; funs.len()],
+        stack: vec![],
+        counter: 0,
+        groups: vec![],
+    };
+    for v in 0..funs.len() {
+        if tarjan.index[v].is_none() {
+            tarjan.visit(v);
+        }
+    }
+    tarjan.groups
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsing::parser::parse;
+
+    fn c(input : &str) -> Result<Checker, TypeError> {
+        let asts = parse(tokenizer::tokenize(FileId(0), input).0).0;
+        let mut checker = Checker::new();
+        checker.check(&asts)?;
+        Ok(checker)
+    }
+
+    fn t(input : &str, name : &str) -> String {
+        c(input).unwrap().type_of(name).unwrap().to_string()
+    }
+
+    #[test]
+    fn should_infer_simple_function() {
+        assert_eq!( t("fun double(x) { add(x, x) }", "double"), "Number -> Number" );
+        assert_eq!( t("fun f(x, y) { (y, x) }", "f"), "(a, b) -> (b, a)" );
+    }
+
+    #[test]
+    fn should_generalize_let() {
+        let output = t(r#"
+fun f() {
+    let id = fun (x) { x };
+    (id(1), id("one"))
+}"#, "f");
+
+        assert_eq!( output, "() -> (Number, String)" );
+    }
+
+    #[test]
+    fn should_generalize_top_level_before_use() {
+        let output = t(r#"
+fun user() { (id(1), id(true)) }
+fun id(x) { x }
+"#, "user");
+
+        assert_eq!( output, "() -> (Number, Bool)" );
+    }
+
+    #[test]
+    fn should_infer_mutual_recursion() {
+        let input = r#"
+fun even(n) { match n { 0 => true, _ => odd(sub(n, 1)) } }
+fun odd(n) { match n { 0 => false, _ => even(sub(n, 1)) } }
+"#;
+        assert_eq!( t(input, "even"), "Number -> Bool" );
+        assert_eq!( t(input, "odd"), "Number -> Bool" );
+    }
+
+    #[test]
+    fn should_infer_data_types() {
+        let input = r#"
+data List<a> = Cons(a, List<a>) | Nil
+fun map(f, xs) {
+    match xs {
+        Nil => Nil,
+        Cons(x, rest) => Cons(f(x), map(f, rest)),
+    }
+}
+fun to_list(xs : [a]) -> List<a> {
+    match xs {
+        [] => Nil,
+        [x | rest] => Cons(x, to_list(rest)),
+    }
+}
+"#;
+        assert_eq!( t(input, "map"), "(a -> b, List<a>) -> List<b>" );
+        assert_eq!( t(input, "to_list"), "[a] -> List<a>" );
+    }
+
+    #[test]
+    fn should_report_mismatch_with_both_spans() {
+        let output = c("fun f() { add(1, \"two\") }");
+
+        match output {
+            Err(TypeError::Mismatch { expected, found, expected_meta, found_meta }) => {
+                assert_eq!( expected, "Number" );
+                assert_eq!( found, "String" );
+                assert_eq!( expected_meta.start, 10 );
+                assert_eq!( found_meta.start, 17 );
+            },
+            _ => panic!("expected mismatch"),
+        }
+    }
+
+    #[test]
+    fn should_report_match_arm_mismatch() {
+        let output = c("fun f(x) { match x { 1 => true, _ => 2 } }");
+
+        assert!( matches!( output, Err(TypeError::Mismatch { expected_meta: Span { start: 26, .. }, found_meta: Span { start: 37, .. }, .. }) ) );
+    }
+
+    #[test]
+    fn should_report_infinite_type() {
+        let output = c("fun f(x) { x(x) }");
+
+        assert!( matches!( output, Err(TypeError::InfiniteType { .. }) ) );
+    }
+
+    #[test]
+    fn should_report_unknown_type_and_arity() {
+        assert!( matches!( c("fun f(x : Blah) { x }"), Err(TypeError::UnknownType(_, _)) ) );
+        assert!( matches!( c("data Box<a> = Box(a) fun f(x : Box) { x }"), Err(TypeError::TypeArity { .. }) ) );
+        assert!( matches!( c("data Box = Box(a)"), Err(TypeError::UnboundTypeVariable(_, _)) ) );
+        assert!( matches!( c("fun f() { Nope(1) }"), Err(TypeError::UnknownConstructor(_, _)) ) );
+    }
+
+    #[test]
+    fn should_infer_chars() {
+        assert_eq!( t("fun f(s) { match chars(s) { ['a' | _] => 'b', _ => 'c' } }", "f"), "String -> Char" );
+        assert_eq!( t("fun g(c : Char) -> String { from_chars([c, c]) }", "g"), "Char -> String" );
+        assert!( matches!( c("fun f() { concat('a', \"b\") }"), Err(TypeError::Mismatch { .. }) ) );
+    }
+
+    #[test]
+    fn should_respect_annotations() {
+        assert!( matches!( c("fun f(x : Number) -> String { x }"), Err(TypeError::Mismatch { .. }) ) );
+        assert_eq!( t("fun f(x : a) -> a { x }", "f"), "a -> a" );
+        assert!( matches!( c("fun f(x : a) -> a { 1 }"), Err(TypeError::Mismatch { .. }) ) );
+        assert!( matches!( c("fun f(x : a, y : b) -> a { y }"), Err(TypeError::Mismatch { .. }) ) );
+        assert_eq!( t("fun f(x : a) -> [a] { [x, x] }\nfun g() { f(1) }", "g"), "() -> [Number]" );
+    }
+
+    #[test]
+    fn should_record_expression_types() {
+        let checker = c("fun f() { [1, 2] }").unwrap();
+        let types = checker.expression_types();
+
+        assert!( types.iter().any(|(m, t)| m.start == 10 && t.to_string() == "[Number]") );
+    }
+}